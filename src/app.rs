@@ -1,19 +1,35 @@
 extern crate pretty_bytes;
 
-use crate::util::stateful::{Named, StatefulList};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use crate::util::stateful::StatefulList;
 
 use crate::config::Config;
+use crate::interface::anonymous::{ANONYMOUS, WELL_KNOWN_APPS};
 use crate::interface::game::Game;
+use crate::interface::login::Prompt;
+use crate::interface::metadata::format_date;
+use crate::util::keymap::{Action, Keymap};
+use crate::util::layout::{DetailRow, PaneLayout};
+use crate::util::query::{quote, Query};
+use crate::util::sort::{nest_dlc, SortOrder};
+use crate::util::theme::Theme;
 
 use pretty_bytes::converter::convert;
 
 use tui::{
-    layout::{Alignment, Constraint, Direction, Layout},
-    style::{Color, Modifier, Style},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::Modifier,
     text::{Span, Spans},
-    widgets::{Block, BorderType, Borders, Cell, List, ListItem, Paragraph, Row, Table},
+    widgets::{
+        Block, BorderType, Borders, Cell, List, ListItem, ListState, Paragraph, Row, Table, Tabs,
+        Wrap,
+    },
 };
 
+// Counts only repeat motions, so anything longer than the biggest library is pointless.
+const MAX_COUNT: usize = 99_999;
+
 const SPLASH: &str = r#"
  . .................................................................................................
   . ................................................................................................
@@ -55,7 +71,179 @@ const SPLASH: &str = r#"
 pub struct App {
     pub mode: Mode,
     pub user: String,
-    pub highlight: Color,
+    pub theme: Theme,
+    pub keymap: Keymap,
+    // Buffer for naming collections and saved searches.
+    pub input: String,
+    pub sidebar: bool,
+    pub collections: ListState,
+    // Outcome of something done in the background, shown in place of the help until a key.
+    pub notice: Option<String>,
+    // Detail overlay on narrow terminals.
+    pub details: bool,
+    pub regions: Regions,
+    // Games picked for bulk actions, and where an open visual range started.
+    pub marked: BTreeSet<i32>,
+    pub anchor: Option<usize>,
+    // Vim style count typed before a motion, e.g. the 10 in `10j`.
+    pub count: Option<usize>,
+    pub pending: Option<Pending>,
+    // Last pattern entered with `?`, cycled through with `n` and `N`.
+    pub find: String,
+    pub tab: Tab,
+    // Game last selected in each tab, restored when switching back.
+    pub selections: BTreeMap<Tab, i32>,
+    // Store details in place of the detail pane, scrolled by lines.
+    pub extended: bool,
+    pub detail_scroll: u16,
+    // What steamcmd asked for while logging in, typed into `input`.
+    pub prompt: Option<Prompt>,
+    // Held until the login goes through, Steam Guard codes need it sent again.
+    pub password: String,
+    pub login_error: Option<String>,
+    // Account picker, with adding an account after the saved ones.
+    pub accounts: ListState,
+    // Anonymous app picker, and the apps picked when it opened to tell if a reload is needed.
+    pub apps: ListState,
+    pub picked: Vec<i32>,
+}
+
+/// Views of the library across the top, each a fixed filter on top of the active collection.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum Tab {
+    Library,
+    Installed,
+    Downloads,
+    Updates,
+    Recent,
+}
+
+impl Tab {
+    pub const ALL: [Tab; 5] = [
+        Tab::Library,
+        Tab::Installed,
+        Tab::Downloads,
+        Tab::Updates,
+        Tab::Recent,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Tab::Library => "Library",
+            Tab::Installed => "Installed",
+            Tab::Downloads => "Downloads",
+            Tab::Updates => "Updates",
+            Tab::Recent => "Recent",
+        }
+    }
+
+    pub fn query(&self) -> &'static str {
+        match self {
+            Tab::Library => "",
+            Tab::Installed => "installed:",
+            Tab::Downloads => "downloading:",
+            Tab::Updates => "update:",
+            Tab::Recent => "played:",
+        }
+    }
+
+    fn index(&self) -> usize {
+        Tab::ALL.iter().position(|tab| tab == self).unwrap_or(0)
+    }
+
+    /// Steps through the tabs, wrapping at either end.
+    pub fn cycle(&self, forward: bool) -> Tab {
+        let count = Tab::ALL.len();
+        Tab::ALL[if forward {
+            (self.index() + 1) % count
+        } else {
+            (self.index() + count - 1) % count
+        }]
+    }
+}
+
+/// First key of a two key motion.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Pending {
+    // `g`, waiting for the second `g`.
+    Top,
+    // Waiting for the letter to jump to.
+    Jump,
+}
+
+/// Clickable actions in the detail pane.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Button {
+    Install,
+    Play,
+    Verify,
+}
+
+impl Button {
+    /// Installs are validated, so verifying is just installing again.
+    pub fn for_game(game: &Game) -> Vec<Button> {
+        if game.is_installed() {
+            vec![Button::Play, Button::Verify]
+        } else {
+            vec![Button::Install]
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Button::Install => " Install ",
+            Button::Play => " Play ",
+            Button::Verify => " Verify ",
+        }
+    }
+}
+
+/// Where the clickable parts of the interface were last drawn.
+#[derive(Default, Clone, Debug)]
+pub struct Regions {
+    pub list: Rect,
+    pub detail: Rect,
+    pub buttons: Vec<(Button, Rect)>,
+    pub tabs: Vec<(Tab, Rect)>,
+}
+
+impl Regions {
+    fn contains(area: Rect, x: u16, y: u16) -> bool {
+        x >= area.x && x < area.x + area.width && y >= area.y && y < area.y + area.height
+    }
+
+    pub fn button(&self, x: u16, y: u16) -> Option<Button> {
+        self.buttons
+            .iter()
+            .find(|(_, area)| Regions::contains(*area, x, y))
+            .map(|(button, _)| *button)
+    }
+
+    pub fn in_detail(&self, x: u16, y: u16) -> bool {
+        Regions::contains(self.detail, x, y)
+    }
+
+    pub fn tab(&self, x: u16, y: u16) -> Option<Tab> {
+        self.tabs
+            .iter()
+            .find(|(_, area)| Regions::contains(*area, x, y))
+            .map(|(tab, _)| *tab)
+    }
+
+    /// Visible row of the game list at the position, inside its borders.
+    pub fn list_row(&self, x: u16, y: u16) -> Option<usize> {
+        let inner = Rect {
+            x: self.list.x + 1,
+            y: self.list.y + 1,
+            width: self.list.width.saturating_sub(2),
+            height: self.list.height.saturating_sub(2),
+        };
+        if Regions::contains(inner, x, y) && !Regions::contains(self.detail, x, y) {
+            Some((y - inner.y) as usize)
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(PartialEq, Clone)]
@@ -65,14 +253,113 @@ pub enum Mode {
     Normal,
     Searching,
     Searched,
+    Collecting,
+    Saving,
+    Confirming,
+    Finding,
+    Accounts,
+    Apps,
     Failed,
     Terminated(String),
 }
 
+/// Entries of the account picker.
+#[derive(PartialEq, Clone, Debug)]
+pub enum AccountEntry {
+    Saved(String),
+    Anonymous,
+    Add,
+}
+
+impl AccountEntry {
+    pub fn from_config(config: &Config) -> Vec<AccountEntry> {
+        let accounts = config.accounts();
+        let anonymous = accounts.iter().any(|user| user == ANONYMOUS);
+        let mut entries: Vec<AccountEntry> =
+            accounts.into_iter().map(AccountEntry::Saved).collect();
+        if !anonymous {
+            entries.push(AccountEntry::Anonymous);
+        }
+        entries.push(AccountEntry::Add);
+        entries
+    }
+
+    pub fn label(&self, config: &Config) -> String {
+        match self {
+            AccountEntry::Saved(user) if *user == config.default_user => format!("● {}", user),
+            AccountEntry::Saved(user) => format!("  {}", user),
+            AccountEntry::Anonymous => "+ Anonymous (dedicated servers and tools)".to_string(),
+            AccountEntry::Add => "+ Add account".to_string(),
+        }
+    }
+}
+
+/// Apps offered to the anonymous login: the well known ones, then any other picked ids.
+pub fn anonymous_apps(config: &Config) -> Vec<(i32, String)> {
+    let mut apps: Vec<(i32, String)> = WELL_KNOWN_APPS
+        .iter()
+        .map(|(id, name)| (*id, name.to_string()))
+        .collect();
+    for id in &config.anonymous_apps {
+        if !apps.iter().any(|(known, _)| known == id) {
+            apps.push((*id, format!("App {}", id)));
+        }
+    }
+    apps
+}
+
+/// Entries of the collection sidebar. Each scopes the game list by a query.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Collection {
+    All,
+    Favorites,
+    Named(String),
+    Saved(String),
+}
+
+impl Collection {
+    pub fn from_config(config: &Config) -> Vec<Collection> {
+        let mut collections = vec![Collection::All, Collection::Favorites];
+        collections.extend(config.collections.keys().cloned().map(Collection::Named));
+        collections.extend(config.saved_queries.keys().cloned().map(Collection::Saved));
+        collections
+    }
+
+    pub fn scope(&self, config: &Config) -> String {
+        match self {
+            Collection::All => "".to_string(),
+            Collection::Favorites => "fav:".to_string(),
+            Collection::Named(name) => format!("col:{}", quote(name)),
+            Collection::Saved(name) => config.saved_queries.get(name).cloned().unwrap_or_default(),
+        }
+    }
+
+    pub fn label(&self, config: &Config) -> String {
+        match self {
+            Collection::All => "All games".to_string(),
+            Collection::Favorites => format!("♡ Favorites ({})", config.favorite_games.len()),
+            Collection::Named(name) => format!(
+                "{} ({})",
+                name,
+                config.collections.get(name).map(Vec::len).unwrap_or(0)
+            ),
+            Collection::Saved(name) => format!("⌕ {}", name),
+        }
+    }
+}
+
 impl App {
-    pub fn new(config: &Config) -> App {
+    pub fn new(config: &Config, theme: Theme) -> App {
         let user = config.default_user.clone();
-        let highlight = config.highlight;
+        let mut collections = ListState::default();
+        collections.select(Some(0));
+        let keymap = Keymap::new(&config.keymap);
+        // Shown until the first key press.
+        let notice = if keymap.conflicts.is_empty() {
+            None
+        } else {
+            Some(keymap.conflicts.join(", "))
+        };
         App {
             mode: if user.is_empty() {
                 Mode::Login
@@ -80,10 +367,167 @@ impl App {
                 Mode::Loading
             },
             user,
-            highlight,
+            theme,
+            keymap,
+            input: "".to_string(),
+            sidebar: !config.collections.is_empty() || !config.saved_queries.is_empty(),
+            collections,
+            notice,
+            details: false,
+            regions: Regions::default(),
+            marked: BTreeSet::new(),
+            anchor: None,
+            count: None,
+            pending: None,
+            find: "".to_string(),
+            tab: Tab::Library,
+            selections: BTreeMap::new(),
+            extended: false,
+            detail_scroll: 0,
+            prompt: None,
+            password: "".to_string(),
+            login_error: None,
+            accounts: ListState::default(),
+            apps: ListState::default(),
+            picked: vec![],
+        }
+    }
+
+    /// Query scoping the game list: the tab's filter within the active collection.
+    pub fn scope(&self, config: &Config) -> String {
+        format!(
+            "{} {}",
+            self.tab.query(),
+            self.collection(config).scope(config)
+        )
+        .trim()
+        .to_string()
+    }
+
+    /// Recent always puts the latest played first, the other tabs follow the configured sort.
+    pub fn sort_order(&self, config: &Config) -> SortOrder {
+        match self.tab {
+            Tab::Recent => SortOrder::LastPlayed,
+            _ => config.sort,
         }
     }
 
+    pub fn sort_games(&self, game_list: &mut StatefulList<Game>, config: &Config) {
+        let sort = self.sort_order(config);
+        game_list.sort_by(|a, b| sort.compare(a, b, config));
+        // Then pull DLC up under their game.
+        let entries = game_list
+            .items()
+            .iter()
+            .map(|game| (game.id, game.parent))
+            .collect::<Vec<(i32, Option<i32>)>>();
+        let positions = nest_dlc(&entries);
+        game_list.sort_by(|a, b| positions.get(&a.id).cmp(&positions.get(&b.id)));
+    }
+
+    /// Hands the list a fresh config snapshot along with the active tab and collection.
+    pub fn scope_games(&self, game_list: &mut StatefulList<Game>, config: &Config) {
+        game_list.set_config(config);
+        game_list.set_scope(self.scope(config));
+        game_list.restart();
+    }
+
+    /// Switches tabs, picking up where the tab was last left.
+    pub fn switch_tab(&mut self, game_list: &mut StatefulList<Game>, config: &Config, tab: Tab) {
+        if let Some(game) = game_list.selected() {
+            self.selections.insert(self.tab, game.id);
+        }
+        let sort = self.sort_order(config);
+        self.tab = tab;
+        if self.sort_order(config) != sort {
+            self.sort_games(game_list, config);
+        }
+        self.scope_games(game_list, config);
+        if let Some(&id) = self.selections.get(&tab) {
+            game_list.select_by(|game| game.id == id);
+        }
+    }
+
+    /// Adds a digit to the count prefix.
+    pub fn push_count(&mut self, digit: u32) {
+        let count = self.count.unwrap_or(0).saturating_mul(10);
+        self.count = Some(count.saturating_add(digit as usize).min(MAX_COUNT));
+    }
+
+    /// Games bulk actions apply to: everything marked, otherwise just the selection.
+    pub fn targets<'a>(&self, game_list: &'a StatefulList<Game>) -> Vec<&'a Game> {
+        if self.marked.is_empty() {
+            game_list.selected().into_iter().collect()
+        } else {
+            game_list
+                .items()
+                .iter()
+                .filter(|game| self.marked.contains(&game.id))
+                .collect()
+        }
+    }
+
+    pub fn toggle_mark(&mut self, id: i32) {
+        if !self.marked.remove(&id) {
+            self.marked.insert(id);
+        }
+    }
+
+    /// Opens a visual range at the selection, or marks everything between its start and the
+    /// selection when one is already open.
+    pub fn visual(&mut self, game_list: &StatefulList<Game>) {
+        let selected = game_list.state.selected();
+        match (self.anchor.take(), selected) {
+            (Some(anchor), Some(selected)) => {
+                let (from, to) = (anchor.min(selected), anchor.max(selected));
+                let games = game_list.activated();
+                for game in games.iter().take(to + 1).skip(from) {
+                    self.marked.insert(game.id);
+                }
+            }
+            (None, selected) => self.anchor = selected,
+            _ => {}
+        }
+    }
+
+    pub fn has_marks(&self) -> bool {
+        !self.marked.is_empty() || self.anchor.is_some()
+    }
+
+    pub fn clear_marks(&mut self) {
+        self.marked.clear();
+        self.anchor = None;
+    }
+
+    /// Currently active sidebar entry, falling back to all games.
+    pub fn collection(&self, config: &Config) -> Collection {
+        let collections = Collection::from_config(config);
+        self.collections
+            .selected()
+            .and_then(|i| collections.get(i).cloned())
+            .unwrap_or(Collection::All)
+    }
+
+    /// Steps through the sidebar, wrapping at either end.
+    pub fn cycle_collection(&mut self, config: &Config, forward: bool) {
+        let count = Collection::from_config(config).len();
+        let i = self.collections.selected().unwrap_or(0).min(count - 1);
+        self.collections.select(Some(if forward {
+            (i + 1) % count
+        } else {
+            (i + count - 1) % count
+        }));
+    }
+
+    /// Drops back to browsing, keeping any committed search.
+    pub fn browse(&mut self, query: &str) {
+        self.mode = if query.is_empty() {
+            Mode::Normal
+        } else {
+            Mode::Searched
+        };
+    }
+
     pub fn build_layout() -> Layout {
         Layout::default()
             .direction(Direction::Vertical)
@@ -94,13 +538,31 @@ impl App {
             .direction(Direction::Vertical)
             .constraints([Constraint::Ratio(1, 1)].as_ref())
     }
-    pub fn build_game_layout() -> Layout {
+    pub fn build_tabs_layout() -> Layout {
+        Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+    }
+    pub fn build_sidebar_layout() -> Layout {
         Layout::default()
             .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(38), Constraint::Percentage(62)].as_ref())
+            .constraints([Constraint::Length(24), Constraint::Min(0)].as_ref())
     }
-    pub fn build_splash_terminated(err: String) -> Paragraph<'static> {
+    pub fn build_game_layout(layout: &PaneLayout) -> Layout {
+        let list = layout.list_percent.min(100);
+        Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(
+                [
+                    Constraint::Percentage(list),
+                    Constraint::Percentage(100 - list),
+                ]
+                .as_ref(),
+            )
+    }
+    pub fn build_splash_terminated(theme: &Theme, err: String) -> Paragraph<'static> {
         App::build_infobox(
+            theme,
             "Oh dear...".to_string(),
             format!(
                 "Something has crashed.. For more details please refer to the error below:\n\n{}",
@@ -110,59 +572,140 @@ impl App {
         )
     }
 
-    pub fn build_splash_err() -> Paragraph<'static> {
+    pub fn build_splash_err(theme: &Theme, reason: Option<String>) -> Paragraph<'static> {
         App::build_infobox(
+            theme,
             "steam-tui".to_string(),
             format!(
-                "{}\n Uhoh. Could not find credentials. Have you logged in?",
-                SPLASH
+                "{}\n Uhoh. {}",
+                SPLASH,
+                reason.unwrap_or_else(|| {
+                    "Could not find credentials. Have you logged in?".to_string()
+                })
             ),
             Alignment::Center,
         )
     }
 
-    pub fn build_splash() -> Paragraph<'static> {
+    pub fn build_splash(theme: &Theme) -> Paragraph<'static> {
         App::build_infobox(
+            theme,
             "steam-tui".to_string(),
             SPLASH.to_string(),
             Alignment::Center,
         )
     }
 
-    pub fn build_patience() -> Paragraph<'static> {
+    pub fn build_placeholder(theme: &Theme) -> Paragraph<'static> {
+        Paragraph::new("Loading artwork...")
+            .style(theme.text().add_modifier(Modifier::DIM))
+            .alignment(Alignment::Center)
+    }
+
+    pub fn build_patience(theme: &Theme) -> Paragraph<'static> {
         App::build_infobox(
+            theme,
             "Welcome".to_string(),
             "Checking cache (on load, you can press 'r' to invalidate cache)".to_string(),
             Alignment::Left,
         )
     }
 
-    fn build_infobox(title: String, content: String, alignment: Alignment) -> Paragraph<'static> {
+    fn build_block(theme: &Theme, title: String) -> Block<'static> {
+        Block::default()
+            .borders(Borders::all())
+            .border_style(theme.border())
+            .title(Span::styled(title, theme.title()))
+            .border_type(BorderType::Plain)
+    }
+
+    fn build_infobox(
+        theme: &Theme,
+        title: String,
+        content: String,
+        alignment: Alignment,
+    ) -> Paragraph<'static> {
         Paragraph::new(content)
-            .style(Style::default())
+            .style(theme.text())
             .alignment(alignment)
-            .block(
-                Block::default()
-                    .borders(Borders::all())
-                    .title(title)
-                    .border_type(BorderType::Plain),
-            )
+            .block(App::build_block(theme, title))
     }
-    pub fn build_query(query: String) -> Paragraph<'static> {
+    pub fn build_query(theme: &Theme, query: String) -> Paragraph<'static> {
         App::build_infobox(
-            "Searching... (press esc to stop)".to_string(),
+            theme,
+            "Searching... (press esc to stop, S to save)".to_string(),
             query,
             Alignment::Left,
         )
     }
-    pub fn build_query_searching(query: String) -> Paragraph<'static> {
+    pub fn build_query_searching(theme: &Theme, query: String) -> Paragraph<'static> {
+        let errors = Query::parse(&query).errors;
+        let title = if errors.is_empty() {
+            "Searching... (press Esc to stop, Enter to commit)".to_string()
+        } else {
+            format!("Searching... (ignoring {})", errors.join(", "))
+        };
+        App::build_infobox(theme, title, query, Alignment::Left)
+    }
+    pub fn build_collecting(theme: &Theme, name: String, config: &Config) -> Paragraph<'static> {
+        let existing = config
+            .collections
+            .keys()
+            .cloned()
+            .collect::<Vec<String>>()
+            .join(", ");
+        let title = if existing.is_empty() {
+            "Add to collection (Enter to toggle, Esc to cancel)".to_string()
+        } else {
+            format!("Add to collection (existing: {})", existing)
+        };
+        App::build_infobox(theme, title, name, Alignment::Left)
+    }
+    pub fn build_saving(theme: &Theme, name: String) -> Paragraph<'static> {
         App::build_infobox(
-            "Searching... (press Esc to stop, Enter to commit)".to_string(),
-            query,
+            theme,
+            "Name this search (Enter to save, Esc to cancel)".to_string(),
+            name,
+            Alignment::Left,
+        )
+    }
+    pub fn build_finding(theme: &Theme, pattern: String) -> Paragraph<'static> {
+        App::build_infobox(
+            theme,
+            "Find (Enter to jump, then n/N for the next and previous match)".to_string(),
+            pattern,
+            Alignment::Left,
+        )
+    }
+    pub fn build_accounts(theme: &Theme) -> Paragraph<'static> {
+        App::build_infobox(
+            theme,
+            "Switch account".to_string(),
+            "Enter to switch, Delete to forget, Esc to go back".to_string(),
+            Alignment::Left,
+        )
+    }
+    pub fn build_apps(theme: &Theme, ids: String) -> Paragraph<'static> {
+        App::build_infobox(
+            theme,
+            "Type app ids or pick below (Enter to add or toggle, Esc to load them)".to_string(),
+            ids,
             Alignment::Left,
         )
     }
-    pub fn build_loaded(count: i32, of: i32) -> Paragraph<'static> {
+    pub fn build_confirming(theme: &Theme, count: usize) -> Paragraph<'static> {
+        App::build_infobox(
+            theme,
+            "Confirm".to_string(),
+            format!(
+                "Uninstall {} game{}? (y to confirm, anything else cancels)",
+                count,
+                if count == 1 { "" } else { "s" }
+            ),
+            Alignment::Left,
+        )
+    }
+    pub fn build_loaded(theme: &Theme, count: i32, of: i32) -> Paragraph<'static> {
         let p = {
             if of < 0 {
                 "Calculating...".to_string()
@@ -171,134 +714,464 @@ impl App {
                 format!("Loading %{:.1}", p)
             }
         };
-        App::build_infobox("Please wait".to_string(), p, Alignment::Left)
+        App::build_infobox(theme, "Please wait".to_string(), p, Alignment::Left)
     }
-    pub fn build_loading() -> Paragraph<'static> {
+    pub fn build_loading(theme: &Theme) -> Paragraph<'static> {
         App::build_infobox(
+            theme,
             "Please wait".to_string(),
             "Logging in and updating...".to_string(),
             Alignment::Left,
         )
     }
-    pub fn build_login(username: String) -> Paragraph<'static> {
-        App::build_infobox(
-            "Login (Enter to submit)".to_string(),
-            username,
-            Alignment::Left,
-        )
+    /// Username, or whatever steamcmd prompted for with passwords masked.
+    pub fn build_login(
+        theme: &Theme,
+        prompt: Option<Prompt>,
+        value: String,
+        error: Option<String>,
+    ) -> Paragraph<'static> {
+        let title = match prompt {
+            Some(prompt) => format!("{} (Enter to submit, Esc to go back)", prompt.label()),
+            None => "Login (Enter to submit)".to_string(),
+        };
+        let title = match error {
+            Some(error) => format!("{}. {}", error, title),
+            None => title,
+        };
+        let value = if prompt.is_some_and(|prompt| prompt.is_secret()) {
+            "*".repeat(value.chars().count())
+        } else {
+            value
+        };
+        App::build_infobox(theme, title, value, Alignment::Left)
     }
-    pub fn build_help() -> Paragraph<'static> {
-        App::build_infobox(
-            "Help".to_string(),
-            "[/] Search | [d]ownload  | [l]ogin | [Enter]xecute | Up (k, w) | Down (j, s) | [q]uit | [Space]team"
-                .to_string(),
-            Alignment::Left,
-        )
+    pub fn build_help(theme: &Theme, keymap: &Keymap) -> Paragraph<'static> {
+        App::build_infobox(theme, "Help".to_string(), keymap.help(), Alignment::Left)
+            .style(theme.help())
+    }
+    pub fn build_notice(theme: &Theme, notice: String) -> Paragraph<'static> {
+        App::build_infobox(theme, "Notice".to_string(), notice, Alignment::Left)
     }
-    pub fn build_terminated_help() -> Paragraph<'static> {
+    pub fn build_terminated_help(theme: &Theme, keymap: &Keymap) -> Paragraph<'static> {
         App::build_infobox(
+            theme,
             "Woops.".to_string(),
-            "Press q to quit.".to_string(),
+            format!("Press {} to quit.", keymap.keys(Action::Quit)),
             Alignment::Left,
         )
     }
 
+    /// Buttons on a single line, along with where each one landed.
+    pub fn build_buttons(
+        theme: &Theme,
+        game: &Game,
+        area: Rect,
+    ) -> (Paragraph<'static>, Vec<(Button, Rect)>) {
+        let mut spans = Vec::new();
+        let mut regions = Vec::new();
+        let mut x = area.x;
+        for button in Button::for_game(game) {
+            let width = button.label().len() as u16;
+            if x + width > area.x + area.width {
+                break;
+            }
+            spans.push(Span::styled(button.label(), theme.highlight()));
+            spans.push(Span::raw(" "));
+            regions.push((button, Rect::new(x, area.y, width, 1)));
+            x += width + 1;
+        }
+        (Paragraph::new(Spans::from(spans)), regions)
+    }
+
+    fn heading(row: &DetailRow) -> &'static str {
+        match row {
+            DetailRow::Homepage => "Homepage",
+            DetailRow::Developer => "Developer",
+            DetailRow::Publisher => "Publisher",
+            DetailRow::ProtonTier => "Proton Tier",
+            DetailRow::Deck => "Steam Deck",
+            DetailRow::Dlc => "DLC",
+            DetailRow::State => "State",
+            DetailRow::Installation => "Installation",
+            DetailRow::Size => "Size",
+        }
+    }
+
+    pub fn render_collections<'a>(theme: &Theme, config: &Config) -> List<'a> {
+        let items: Vec<_> = Collection::from_config(config)
+            .iter()
+            .map(|collection| ListItem::new(Span::styled(collection.label(config), theme.text())))
+            .collect();
+        List::new(items)
+            .block(App::build_block(theme, "Collections".to_string()))
+            .highlight_style(theme.highlight())
+    }
+
+    pub fn render_accounts<'a>(theme: &Theme, config: &Config) -> List<'a> {
+        let items: Vec<_> = AccountEntry::from_config(config)
+            .iter()
+            .map(|entry| {
+                let style = match entry {
+                    AccountEntry::Saved(user) if *user == config.default_user => theme.title(),
+                    AccountEntry::Saved(_) => theme.text(),
+                    _ => theme.help(),
+                };
+                ListItem::new(Span::styled(entry.label(config), style))
+            })
+            .collect();
+        List::new(items)
+            .block(App::build_block(theme, "Accounts".to_string()))
+            .highlight_style(theme.highlight())
+    }
+
+    pub fn render_apps<'a>(theme: &Theme, config: &Config) -> List<'a> {
+        let items: Vec<_> = anonymous_apps(config)
+            .into_iter()
+            .map(|(id, name)| {
+                let picked = config.anonymous_apps.contains(&id);
+                ListItem::new(Span::styled(
+                    format!("{} {} ({})", if picked { "✓" } else { " " }, name, id),
+                    if picked { theme.title() } else { theme.text() },
+                ))
+            })
+            .collect();
+        List::new(items)
+            .block(App::build_block(theme, "Anonymous apps".to_string()))
+            .highlight_style(theme.highlight())
+    }
+
+    /// Tabs along with where each title lands, following the padding and divider `Tabs` uses.
+    pub fn render_tabs<'a>(theme: &Theme, tab: Tab, area: Rect) -> (Tabs<'a>, Vec<(Tab, Rect)>) {
+        let mut areas = vec![];
+        let mut x = area.x;
+        for tab in Tab::ALL.iter() {
+            x += 1;
+            let width = (tab.label().len() as u16).min((area.x + area.width).saturating_sub(x));
+            areas.push((*tab, Rect::new(x, area.y, width, 1)));
+            // Padding and the divider.
+            x += width + 2;
+        }
+        let titles = Tab::ALL
+            .iter()
+            .map(|tab| Spans::from(Span::styled(tab.label(), theme.text())))
+            .collect();
+        let tabs = Tabs::new(titles)
+            .select(tab.index())
+            .style(theme.border())
+            .highlight_style(theme.highlight());
+        (tabs, areas)
+    }
+
+    /// Everything ProtonDB knows about the game, colored by tier.
+    pub fn proton_spans(theme: &Theme, game: &Game) -> Spans<'static> {
+        let mut spans = vec![];
+        if game.is_native() {
+            spans.push(Span::styled("native Linux", theme.tier("native")));
+            spans.push(Span::raw(", "));
+        }
+        match game.proton_summary() {
+            Some(data) => {
+                spans.push(Span::styled(data.format(), theme.tier(&data.tier)));
+                spans.push(Span::raw(", trending "));
+                spans.push(Span::styled(
+                    data.trendingTier.clone(),
+                    theme.tier(&data.trendingTier),
+                ));
+                spans.push(Span::raw(", best "));
+                spans.push(Span::styled(
+                    data.bestReportedTier.clone(),
+                    theme.tier(&data.bestReportedTier),
+                ));
+                spans.push(Span::raw(format!(
+                    " ({} reports, {} confidence)",
+                    data.total, data.confidence
+                )));
+            }
+            None => spans.push(Span::raw(game.get_proton())),
+        }
+        Spans::from(spans)
+    }
+
+    /// Sections of the extended details, skipping anything the app info doesn't have.
+    pub fn metadata_lines(theme: &Theme, keymap: &Keymap, game: &Game) -> Vec<Spans<'static>> {
+        let metadata = &game.metadata;
+        let mut lines = vec![Spans::from(Span::styled(game.name.clone(), theme.title()))];
+        let mut section = |title: &str, values: Vec<String>| {
+            if values.is_empty() {
+                return;
+            }
+            lines.push(Spans::from(""));
+            lines.push(Spans::from(Span::styled(
+                title.to_string(),
+                theme.heading(),
+            )));
+            for value in values {
+                lines.push(Spans::from(Span::styled(
+                    format!("  {}", value),
+                    theme.text(),
+                )));
+            }
+        };
+        let released = game.release_date.map(format_date);
+        section(
+            "Release",
+            match (released, &metadata.release_state) {
+                (Some(date), Some(state)) => vec![format!("{} ({})", date, state)],
+                (Some(date), None) => vec![date],
+                (None, Some(state)) => vec![state.clone()],
+                (None, None) => vec![],
+            },
+        );
+        section(
+            "Genres",
+            Some(metadata.genres.join(", "))
+                .filter(|genres| !genres.is_empty())
+                .into_iter()
+                .collect(),
+        );
+        section("Features", metadata.categories.clone());
+        section(
+            "Steam Deck",
+            Some(metadata.deck.category.label().to_string())
+                .into_iter()
+                .chain(
+                    metadata.deck.tests.iter().map(|test| {
+                        format!("{} {}", if test.passed { "✓" } else { "!" }, test.note)
+                    }),
+                )
+                .collect(),
+        );
+        section(
+            "Controller support",
+            metadata.controller_support.iter().cloned().collect(),
+        );
+        section(
+            "Metacritic",
+            metadata
+                .metacritic
+                .map(|score| score.to_string())
+                .into_iter()
+                .chain(metadata.metacritic_url.iter().cloned())
+                .collect(),
+        );
+        section(
+            "Platforms",
+            Some(metadata.os_list.join(", "))
+                .filter(|os| !os.is_empty())
+                .into_iter()
+                .collect(),
+        );
+        section(
+            "Languages",
+            metadata
+                .languages
+                .iter()
+                .map(|language| {
+                    if language.full_audio {
+                        format!("{} (full audio)", language.name)
+                    } else {
+                        language.name.clone()
+                    }
+                })
+                .collect(),
+        );
+        if lines.len() == 1 {
+            lines.push(Spans::from(""));
+            lines.push(Spans::from(Span::styled(
+                format!(
+                    "No store details cached, press {} to refresh.",
+                    keymap.keys(Action::Refresh)
+                ),
+                theme.help(),
+            )));
+        }
+        lines
+    }
+
+    pub fn build_metadata(
+        theme: &Theme,
+        keymap: &Keymap,
+        lines: Vec<Spans<'static>>,
+        scroll: u16,
+    ) -> Paragraph<'static> {
+        Paragraph::new(lines)
+            .style(theme.text())
+            .block(App::build_block(
+                theme,
+                format!(
+                    "Details ({}/{} to scroll, {} to close)",
+                    keymap.keys(Action::ScrollDetailsDown),
+                    keymap.keys(Action::ScrollDetailsUp),
+                    keymap.keys(Action::Extended)
+                ),
+            ))
+            .wrap(Wrap { trim: false })
+            .scroll((scroll, 0))
+    }
+
+    /// Number of games queued or downloading, and their mean progress.
+    pub fn downloads(game_list: &StatefulList<Game>) -> Option<(usize, f64)> {
+        let progress = game_list
+            .items()
+            .iter()
+            .filter_map(Game::download_progress)
+            .collect::<Vec<f64>>();
+        if progress.is_empty() {
+            None
+        } else {
+            Some((
+                progress.len(),
+                progress.iter().sum::<f64>() / progress.len() as f64,
+            ))
+        }
+    }
+
     pub fn render_games<'a>(
-        highlight: Color,
+        theme: &Theme,
+        keymap: &Keymap,
         game_list: &StatefulList<Game>,
+        sort: SortOrder,
+        marked: &BTreeSet<i32>,
+        visual: bool,
     ) -> (List<'a>, Table<'a>) {
-        let games = Block::default()
-            .borders(Borders::ALL)
-            .title("Games")
-            .border_type(BorderType::Plain);
+        let mut title = format!("Games (by {})", sort.label());
+        if visual {
+            title.push_str(" | Visual");
+        }
+        if !marked.is_empty() {
+            title.push_str(&format!(" | {} marked", marked.len()));
+        }
+        if let Some((count, progress)) = App::downloads(game_list) {
+            title.push_str(&format!(" | Downloading {} ({:.0}%)", count, progress));
+        }
+        let games = App::build_block(theme, title);
 
+        // DLC hang off their game, which can be folded to hide them.
+        let config = game_list.config();
+        let ids = game_list
+            .items()
+            .iter()
+            .map(|game| game.id)
+            .collect::<HashSet<i32>>();
+        let parents = game_list
+            .items()
+            .iter()
+            .filter(|game| game.is_dlc())
+            .filter_map(|game| game.parent.filter(|parent| ids.contains(parent)))
+            .collect::<HashSet<i32>>();
         let items: Vec<_> = game_list
             .activated()
             .iter()
             .map(|game| {
-                let modifier = {
-                    if let Some(status) = game.get_status() {
-                        if status.state == "uninstalled" || status.state.contains("Failed") {
-                            Modifier::DIM
-                        } else {
-                            Modifier::BOLD
-                        }
-                    } else {
-                        Modifier::DIM
-                    }
+                let mark = if marked.contains(&game.id) {
+                    "● "
+                } else {
+                    ""
+                };
+                let branch = match game.parent {
+                    _ if config.hide_dlc => "",
+                    Some(parent) if game.is_dlc() && ids.contains(&parent) => "  └ ",
+                    _ if !parents.contains(&game.id) => "",
+                    _ if config.collapsed_dlc.contains(&game.id) => "▸ ",
+                    _ => "▾ ",
                 };
                 ListItem::new(Spans::from(vec![Span::styled(
-                    game.get_name(),
-                    Style::default().add_modifier(modifier),
+                    format!("{}{}{}", mark, branch, game_list.name(game)),
+                    theme.game(game),
                 )]))
             })
             .collect();
 
         let list = List::new(items)
             .block(games)
-            .highlight_style(Style::default().bg(highlight).add_modifier(Modifier::BOLD));
+            .highlight_style(theme.highlight());
 
         let details = match game_list.selected() {
             Some(selected) => {
-                selected.query_proton();
-
                 let spacer = Row::new(vec![Cell::from(Span::raw(" "))]);
                 // Construct table head (id, name)
                 let mut table = vec![
                     Row::new(vec![
-                        Cell::from(Span::styled(
-                            "ID",
-                            Style::default().add_modifier(Modifier::BOLD),
-                        )),
-                        Cell::from(Span::styled(
-                            "Name",
-                            Style::default().add_modifier(Modifier::BOLD),
-                        )),
+                        Cell::from(Span::styled("ID", theme.heading())),
+                        Cell::from(Span::styled("Name", theme.heading())),
                     ]),
                     Row::new(vec![
                         Cell::from(Span::raw(selected.id.to_string())),
-                        Cell::from(Span::raw(selected.get_name())),
+                        Cell::from(Span::raw(game_list.name(selected))),
                     ]),
                     spacer.clone(),
                 ];
                 // Construct table details
-                for &(heading, value) in &[
-                    ("Homepage", &selected.homepage),
-                    ("Developer", &selected.developer),
-                    ("Publisher", &selected.publisher),
-                    ("Proton Tier", &selected.get_proton()),
-                ] {
+                let layout = &game_list.config().layout;
+                let status = selected.get_status();
+                let dlc = selected.owned_dlc(game_list.items());
+                let mut spaced = false;
+                for row in &layout.detail_rows {
+                    let value = match (row, &status) {
+                        (DetailRow::Dlc, _) if !dlc.is_empty() => {
+                            let installed = dlc.iter().filter(|game| game.is_installed()).count();
+                            table.push(Row::new(vec![
+                                Cell::from(Span::styled(App::heading(row), theme.heading())),
+                                Cell::from(Span::raw(format!(
+                                    "{} owned, {} installed, {} released",
+                                    dlc.len(),
+                                    installed,
+                                    selected.dlc.len().max(dlc.len())
+                                ))),
+                            ]));
+                            for game in &dlc {
+                                let mark = if game.is_installed() { "✓" } else { " " };
+                                table.push(Row::new(vec![
+                                    Cell::from(Span::raw("")),
+                                    Cell::from(Span::styled(
+                                        format!("{} {}", mark, game.name),
+                                        theme.game(game),
+                                    )),
+                                ]));
+                            }
+                            continue;
+                        }
+                        (DetailRow::Homepage, _) => selected.homepage.clone(),
+                        (DetailRow::Developer, _) => selected.developer.clone(),
+                        (DetailRow::Publisher, _) => selected.publisher.clone(),
+                        (DetailRow::ProtonTier, _) => {
+                            table.push(Row::new(vec![
+                                Cell::from(Span::styled(App::heading(row), theme.heading())),
+                                Cell::from(App::proton_spans(theme, selected)),
+                            ]));
+                            continue;
+                        }
+                        (DetailRow::Deck, _) => {
+                            let deck = &selected.metadata.deck;
+                            match deck.tests.iter().filter(|test| !test.passed).count() {
+                                0 => deck.category.label().to_string(),
+                                notes => format!(
+                                    "{} ({} notes, {} for details)",
+                                    deck.category.label(),
+                                    notes,
+                                    keymap.keys(Action::Extended)
+                                ),
+                            }
+                        }
+                        (DetailRow::State, Some(status)) => status.state.clone(),
+                        (DetailRow::Installation, Some(status)) => status.installdir.clone(),
+                        (DetailRow::Size, Some(status)) => convert(status.size),
+                        _ => continue,
+                    };
+                    // Install details are set apart from the rest.
+                    if row.needs_status() && !spaced {
+                        table.push(spacer.clone());
+                        spaced = true;
+                    }
                     table.push(Row::new(vec![
-                        Cell::from(Span::styled(
-                            heading,
-                            Style::default().add_modifier(Modifier::BOLD),
-                        )),
-                        Cell::from(Span::raw(value.clone())),
+                        Cell::from(Span::styled(App::heading(row), theme.heading())),
+                        Cell::from(Span::raw(value)),
                     ]));
                 }
-                if let Some(status) = selected.get_status() {
-                    table.push(spacer.clone());
-                    for &(heading, value) in &[
-                        ("State", &status.state),
-                        ("Installation", &status.installdir),
-                        ("Size", &convert(status.size)),
-                    ] {
-                        table.push(Row::new(vec![
-                            Cell::from(Span::styled(
-                                heading,
-                                Style::default().add_modifier(Modifier::BOLD),
-                            )),
-                            Cell::from(Span::raw(value.clone())),
-                        ]));
-                    }
-                }
                 Table::new(table)
-                    .block(
-                        Block::default()
-                            .borders(Borders::ALL)
-                            .title("Detail")
-                            .border_type(BorderType::Plain),
-                    )
+                    .style(theme.text())
+                    .block(App::build_block(theme, "Detail".to_string()))
                     .widths(&[Constraint::Percentage(15), Constraint::Percentage(85)])
             }
             None => Table::new(vec![Row::new(vec![Cell::from(Span::raw(
@@ -308,3 +1181,149 @@ impl App {
         (list, details)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::app::{App, Collection, Tab};
+    use crate::config::Config;
+    use crate::interface::game::Game;
+    use crate::util::query::{Filter, Query};
+    use crate::util::stateful::StatefulList;
+    use crate::util::theme::Theme;
+
+    fn ids(games: Vec<&Game>) -> Vec<i32> {
+        games.iter().map(|game| game.id).collect()
+    }
+
+    #[test]
+    fn test_marks_and_targets() {
+        let config = Config::default();
+        let games = (1..=5)
+            .map(|id| Game::fixture(id, &format!("Game {}", id), None))
+            .collect();
+        let mut list = StatefulList::with_items(games, &config);
+        let mut app = App::new(&config, Theme::dark());
+        list.select(1);
+        // Without marks, only the selection.
+        assert_eq!(ids(app.targets(&list)), vec![2]);
+
+        app.toggle_mark(4);
+        app.toggle_mark(1);
+        assert_eq!(ids(app.targets(&list)), vec![1, 4]);
+        app.toggle_mark(4);
+        assert_eq!(ids(app.targets(&list)), vec![1]);
+
+        // A visual range marks from where it was opened to the selection, either way round.
+        list.select(4);
+        app.visual(&list);
+        assert!(app.has_marks());
+        list.select(2);
+        app.visual(&list);
+        assert_eq!(ids(app.targets(&list)), vec![1, 3, 4, 5]);
+        assert!(app.anchor.is_none());
+
+        app.clear_marks();
+        assert!(!app.has_marks());
+        assert_eq!(ids(app.targets(&list)), vec![3]);
+    }
+
+    #[test]
+    fn test_switch_tab() {
+        let mut config = Config::default();
+        config.last_played.insert(3, 100);
+        config.last_played.insert(1, 200);
+        let games = vec![
+            Game::fixture(1, "Game 1", None),
+            Game::fixture(2, "Game 2", Some(("Fully Installed", 0.))),
+            Game::fixture(3, "Game 3", None),
+            Game::fixture(4, "Game 4", Some(("Fully Installed", 0.))),
+        ];
+        let mut list = StatefulList::with_items(games, &config);
+        let mut app = App::new(&config, Theme::dark());
+        app.sort_games(&mut list, &config);
+        app.scope_games(&mut list, &config);
+        let selected = |list: &StatefulList<Game>| list.selected().map(|game| game.id);
+        let activated = |list: &StatefulList<Game>| {
+            list.activated()
+                .iter()
+                .map(|game| game.id)
+                .collect::<Vec<i32>>()
+        };
+        list.select(2);
+
+        // Tabs start at the top, then remember where they were left.
+        app.switch_tab(&mut list, &config, Tab::Installed);
+        assert_eq!(activated(&list), vec![2, 4]);
+        assert_eq!(selected(&list), Some(2));
+        list.select(1);
+        app.switch_tab(&mut list, &config, Tab::Library);
+        assert_eq!(selected(&list), Some(3));
+        app.switch_tab(&mut list, &config, Tab::Installed);
+        assert_eq!(selected(&list), Some(4));
+
+        // Recent is latest played first whatever the sort, and the others go back to it.
+        app.switch_tab(&mut list, &config, Tab::Recent);
+        assert_eq!(activated(&list), vec![1, 3]);
+        app.switch_tab(&mut list, &config, Tab::Library);
+        assert_eq!(activated(&list), vec![1, 2, 3, 4]);
+        assert_eq!(selected(&list), Some(3));
+    }
+
+    #[test]
+    fn test_download_progress() {
+        let config = Config::default();
+        let queued = Game::fixture(1, "Game 1", Some(("queued", 0.)));
+        let downloading = Game::fixture(2, "Game 2", Some(("downloading 50%", 0.)));
+        let installed = Game::fixture(3, "Game 3", Some(("Fully Installed", 0.)));
+        assert_eq!(queued.download_progress(), Some(0.));
+        assert_eq!(downloading.download_progress(), Some(50.));
+        assert_eq!(installed.download_progress(), None);
+        // Nothing's on disk until the download finishes.
+        assert!(!queued.is_installed());
+        assert!(!downloading.is_installed());
+        assert!(installed.is_installed());
+
+        let list = StatefulList::with_items(vec![queued, downloading, installed], &config);
+        assert_eq!(App::downloads(&list), Some((2, 25.)));
+        let list = StatefulList::with_items(vec![Game::fixture(4, "Game 4", None)], &config);
+        assert_eq!(App::downloads(&list), None);
+    }
+
+    #[test]
+    fn test_collections() {
+        let mut config = Config::default();
+        config.toggle_collection("Say \"co-op\"", 620);
+        config
+            .saved_queries
+            .insert("Small".to_string(), "size:<1g".to_string());
+        let collections = Collection::from_config(&config);
+        assert_eq!(
+            collections,
+            vec![
+                Collection::All,
+                Collection::Favorites,
+                Collection::Named("Say \"co-op\"".to_string()),
+                Collection::Saved("Small".to_string()),
+            ]
+        );
+        // Quotes in a name survive the trip through the search bar.
+        let scope = Query::parse(&collections[2].scope(&config));
+        assert_eq!(
+            scope.terms[0].filter,
+            Filter::Collection("say \"co-op\"".to_string())
+        );
+        assert_eq!(collections[3].scope(&config), "size:<1g");
+
+        let mut app = App::new(&config, Theme::dark());
+        app.cycle_collection(&config, false);
+        assert_eq!(
+            app.collection(&config),
+            Collection::Saved("Small".to_string())
+        );
+        app.cycle_collection(&config, true);
+        assert_eq!(app.collection(&config), Collection::All);
+        app.cycle_collection(&config, true);
+        app.cycle_collection(&config, true);
+        assert_eq!(app.scope(&config), r#"col:"Say \"co-op\"""#);
+    }
+}
@@ -1,5 +1,11 @@
 use crate::interface::{
-    account::Account, executable::*, game::Game, game_status::*, steam_cmd::SteamCmd,
+    account::Account,
+    anonymous::ANONYMOUS,
+    executable::*,
+    game::Game,
+    game_status::*,
+    login::{login_command, LoginFailure, LoginOutcome, Prompt},
+    steam_cmd::{Console, SteamCmd},
 };
 
 use crate::util::{
@@ -17,30 +23,42 @@ use port_scanner::scan_port;
 use std::process;
 use std::sync::Arc;
 
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::VecDeque;
 use std::fs;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::Mutex;
 use std::thread;
+use std::time::{Duration, Instant};
 
 const STEAM_PORT: u16 = 57343;
+// How long to wait on steamcmd for an app's info, ids it doesn't know never get any.
+const APP_INFO_TIMEOUT: Duration = Duration::from_secs(10);
+const APP_INFO_POLL: Duration = Duration::from_millis(250);
 
 #[derive(PartialEq, Clone)]
 pub enum State {
     LoggedOut,
     LoggedIn,
-    Failed,
+    Prompt(Prompt),
+    Failed(LoginFailure),
     Terminated(String),
     Loaded(i32, i32),
 }
 
 pub enum Command {
     Cli(String),
+    // User, then the password and Steam Guard code when steamcmd asked for them.
+    Login(String, Option<String>, Option<String>),
     Install(i32, Arc<Mutex<Option<GameStatus>>>),
+    Uninstall(i32, Arc<Mutex<Option<GameStatus>>>),
     Run(i32, Vec<Executable>, Arc<Mutex<Option<GameStatus>>>),
     StartClient,
-    Restart,
+    // Starts a fresh steamcmd logged in as the given user.
+    Restart(String),
+    // Loads the given apps in place of the account's licenses.
+    LoadApps(Vec<i32>),
 }
 
 fn execute(
@@ -52,10 +70,28 @@ fn execute(
     let mut queue = VecDeque::new();
     let mut games = Vec::new();
     let mut account: Option<Account> = None;
-
-    // TODO(#20) Pass in Arcs for download status into threads. If requested state is in
-    // downloading, show satus.
-    let mut downloading: HashSet<i32> = HashSet::new();
+    // Who the games being loaded belong to, each account has its own cache.
+    let mut user = String::new();
+    // Whether the last login sent a password and a code, to tell a wrong one from a missing one.
+    let mut attempt = (false, false);
+    // Statuses of games being removed, updated once steamcmd answers.
+    let mut uninstalling: HashMap<i32, Arc<Mutex<Option<GameStatus>>>> = HashMap::new();
+
+    // Downloads run one at a time off a single queue, so bulk installs don't fight over
+    // bandwidth and their progress can be summed up.
+    let downloading: Arc<Mutex<HashSet<i32>>> = Arc::new(Mutex::new(HashSet::new()));
+    let (downloads, pending) = channel::<(String, i32, Arc<Mutex<Option<GameStatus>>>)>();
+    {
+        let downloading = downloading.clone();
+        thread::spawn(move || {
+            for (name, id, status) in pending {
+                download(name, id, status);
+                if let Ok(mut downloading) = downloading.lock() {
+                    downloading.remove(&id);
+                }
+            }
+        });
+    }
 
     // Cleanup the steam process if steam-tui quits.
     let mut cleanup: Option<Sender<bool>> = None;
@@ -97,92 +133,57 @@ fn execute(
                         });
                     }
                 }
-                Some(Command::Restart) => {
+                Some(Command::Restart(login)) => {
                     let mut state = state.lock()?;
                     *state = State::LoggedOut;
                     cmd = SteamCmd::new()?;
-                    let user = match account {
-                        Some(ref acct) => acct.account.clone(),
-                        _ => "".to_string(),
-                    };
-                    queue.push_front(Command::Cli(format!("login {}", user)));
+                    account = None;
+                    games = Vec::new();
+                    queue.push_front(Command::Login(login, None, None));
+                }
+                Some(Command::Login(login, password, code)) => {
+                    attempt = (password.is_some(), code.is_some());
+                    match login_command(&login, &password, &code) {
+                        Ok(line) => queue.push_front(Command::Cli(line)),
+                        Err(failure) => *state.lock()? = State::Failed(failure),
+                    }
+                    user = login;
+                }
+                Some(Command::LoadApps(ids)) => {
+                    games = Vec::new();
+                    let mut state = state.lock()?;
+                    if ids.is_empty() {
+                        fs::write(cache_location(&user)?, serde_json::to_string(&games)?)?;
+                        *state = State::LoggedIn;
+                        continue;
+                    }
+                    *state = State::Loaded(0, ids.len() as i32);
+                    for id in ids {
+                        queue.push_front(Command::Cli(format!("app_info_print {}", id)));
+                    }
                 }
                 Some(Command::Install(id, status)) => {
                     if let Some(ref acct) = account {
-                        if downloading.contains(&id) {
+                        if !downloading.lock()?.insert(id) {
                             continue;
                         }
-                        downloading.insert(id);
-                        let name = acct.account.clone();
-                        thread::spawn(move || {
-                            {
-                                let mut reference = status.lock().unwrap();
-                                *reference = Some(GameStatus::msg(&*reference, "processing..."));
-                            }
-                            match SteamCmd::script(
-                                install_script_location(name.clone(), id)
-                                    .unwrap()
-                                    .to_str()
-                                    .expect("Installation thread failed."),
-                            ) {
-                                Ok(mut cmd) => {
-                                    // Scrub past unused data.
-                                    for _ in 1..15 {
-                                        cmd.next();
-                                    }
-                                    while let Ok(buf) = cmd.maybe_next() {
-                                        let response = String::from_utf8_lossy(&buf);
-                                        // TODO: Investigate why download updates don't seem to
-                                        // appear...
-                                        match *INSTALL_LEX.tokenize(&response).as_slice() {
-                                            ["Update", a, b] => {
-                                                let a = a.parse::<f64>().unwrap_or(0.);
-                                                let b = b.parse::<f64>().unwrap_or(1.);
-                                                let mut reference = status.lock().unwrap();
-                                                let update =
-                                                    format!("downloading {}%", 100. * a / b);
-                                                *reference =
-                                                    Some(GameStatus::msg(&*reference, &update));
-                                            }
-                                            ["ERROR", msg] => {
-                                                let mut reference = status.lock().unwrap();
-                                                let update = format!("Failed: {}", msg);
-                                                *reference =
-                                                    Some(GameStatus::msg(&*reference, &update));
-                                            }
-                                            ["Success"] => {
-                                                let mut reference = status.lock().unwrap();
-                                                let size = match &*reference {
-                                                    Some(gs) => gs.size,
-                                                    _ => 0.,
-                                                };
-                                                *reference = Some(GameStatus {
-                                                    state: "Success!".to_string(),
-                                                    installdir: "".to_string(),
-                                                    size,
-                                                });
-                                                // TODO: call app_status and update after success.
-                                            }
-                                            _ => {
-                                                log!("unmatched", response);
-                                            }
-                                        }
-                                    }
-                                }
-                                Err(err) => {
-                                    let err = format!("{:?}", err);
-                                    let mut reference = status.lock().unwrap();
-                                    *reference = Some(GameStatus {
-                                        state: format!("Failed: {}", err),
-                                        installdir: "".to_string(),
-                                        size: 0.,
-                                    });
-                                    log!("Install script for:", name, "failed", err);
-                                }
-                            }
-                        });
+                        {
+                            let mut reference = status.lock()?;
+                            *reference = Some(GameStatus::msg(&reference, "queued"));
+                        }
+                        downloads
+                            .send((acct.account.clone(), id, status))
+                            .map_err(|err| STError::Problem(format!("{:?}", err)))?;
                     };
                 }
+                Some(Command::Uninstall(id, status)) => {
+                    queue.push_front(Command::Cli(format!("app_uninstall {}", id)));
+                    {
+                        let mut reference = status.lock()?;
+                        *reference = Some(GameStatus::msg(&reference, "uninstalling..."));
+                    }
+                    uninstalling.insert(id, status);
+                }
                 Some(Command::Run(id, executables, status)) => {
                     {
                         let mut reference = status.lock().unwrap();
@@ -291,11 +292,26 @@ fn execute(
                             // BUG TEMP FIX: Scrub unhandled lines
                             let mut response = response.to_string();
                             (cmd, response) = scrub_past_responses(cmd, response, &["[0m", "[1m\nSteam>"], None);
-                            if response.contains("Login Failure") || response.contains("FAILED") {
-                                let mut state = state.lock()?;
-                                *state = State::Failed;
-                            } else {
-                                queue.push_front(Command::Cli("info".to_string()));
+                            let outcome =
+                                LoginOutcome::parse(&response).for_attempt(attempt.0, attempt.1);
+                            attempt = (false, false);
+                            match outcome {
+                                LoginOutcome::Success if user == ANONYMOUS => {
+                                    account = Some(Account::anonymous());
+                                    let mut state = state.lock()?;
+                                    *state = State::Loaded(0, -2);
+                                }
+                                LoginOutcome::Success => {
+                                    queue.push_front(Command::Cli("info".to_string()))
+                                }
+                                LoginOutcome::Prompt(prompt) => {
+                                    let mut state = state.lock()?;
+                                    *state = State::Prompt(prompt);
+                                }
+                                LoginOutcome::Failure(failure) => {
+                                    let mut state = state.lock()?;
+                                    *state = State::Failed(failure);
+                                }
                             }
                             log!("login");
                         }
@@ -363,17 +379,11 @@ fn execute(
                             // Bug requires additional scan
                             // do a proper check here in case this is ever fixed.
                             // A bit of a hack, but will do for now.
-                            let mut response = response;
+                            let mut response = response.to_string();
                             log!(response);
                             if response == "[0m" {
-                                cmd.write("")?;
-                                cmd.write("")?;
-                                while !response.starts_with("[0mAppID") {
-                                    if let Ok(buf) = cmd.maybe_next() {
-                                        response =
-                                            String::from_utf8_lossy(&buf).into_owned().into();
-                                    }
-                                }
+                                response = await_app_info(&mut cmd, response, APP_INFO_TIMEOUT)
+                                    .unwrap_or_default();
                             }
                             let mut lines = response.lines();
 
@@ -388,6 +398,17 @@ fn execute(
                                 }
                             };
                         }
+                        ["app_uninstall", id] => {
+                            log!("app_uninstall", response);
+                            let status = id
+                                .parse::<i32>()
+                                .ok()
+                                .and_then(|id| uninstalling.remove(&id));
+                            if let Some(status) = status {
+                                let mut reference = status.lock()?;
+                                *reference = Some(GameStatus::uninstall(&reference, &response));
+                            }
+                        }
                         ["app_status", _id] => {
                             sender.send(response.to_string())?;
                         }
@@ -412,15 +433,14 @@ fn execute(
                     // If in Loading state, update progress.
                     let mut state = state.lock()?;
                     if let State::Loaded(o, e) = *state {
-                        updated += o;
-                        let total = e + (queue.len() - waiting) as i32;
-                        *state = if updated == total {
-                            games.sort_by(|a, b| a.name.cmp(&b.name));
-                            fs::write(cache_location()?, serde_json::to_string(&games)?)?;
-                            games = Vec::new();
-                            State::LoggedIn
-                        } else {
-                            State::Loaded(updated, total)
+                        *state = match progress(o, e, updated, (queue.len() - waiting) as i32) {
+                            Some((loaded, total)) => State::Loaded(loaded, total),
+                            None => {
+                                games.sort_by(|a, b| a.name.cmp(&b.name));
+                                fs::write(cache_location(&user)?, serde_json::to_string(&games)?)?;
+                                games = Vec::new();
+                                State::LoggedIn
+                            }
                         }
                     }
                     // Iterate to scrub past Steam> prompt
@@ -432,6 +452,70 @@ fn execute(
     }
 }
 
+fn download(name: String, id: i32, status: Arc<Mutex<Option<GameStatus>>>) {
+    {
+        let mut reference = status.lock().unwrap();
+        *reference = Some(GameStatus::msg(&*reference, "processing..."));
+    }
+    match SteamCmd::script(
+        install_script_location(name.clone(), id)
+            .unwrap()
+            .to_str()
+            .expect("Installation thread failed."),
+    ) {
+        Ok(mut cmd) => {
+            // Scrub past unused data.
+            for _ in 1..15 {
+                cmd.next();
+            }
+            while let Ok(buf) = cmd.maybe_next() {
+                let response = String::from_utf8_lossy(&buf);
+                // TODO: Investigate why download updates don't seem to appear...
+                match *INSTALL_LEX.tokenize(&response).as_slice() {
+                    ["Update", a, b] => {
+                        let a = a.parse::<f64>().unwrap_or(0.);
+                        let b = b.parse::<f64>().unwrap_or(1.);
+                        let mut reference = status.lock().unwrap();
+                        let update = format!("downloading {}%", 100. * a / b);
+                        *reference = Some(GameStatus::msg(&*reference, &update));
+                    }
+                    ["ERROR", msg] => {
+                        let mut reference = status.lock().unwrap();
+                        let update = format!("Failed: {}", msg);
+                        *reference = Some(GameStatus::msg(&*reference, &update));
+                    }
+                    ["Success"] => {
+                        let mut reference = status.lock().unwrap();
+                        let size = match &*reference {
+                            Some(gs) => gs.size,
+                            _ => 0.,
+                        };
+                        *reference = Some(GameStatus {
+                            state: "Success!".to_string(),
+                            installdir: "".to_string(),
+                            size,
+                        });
+                        // TODO: call app_status and update after success.
+                    }
+                    _ => {
+                        log!("unmatched", response);
+                    }
+                }
+            }
+        }
+        Err(err) => {
+            let err = format!("{:?}", err);
+            let mut reference = status.lock().unwrap();
+            *reference = Some(GameStatus {
+                state: format!("Failed: {}", err),
+                installdir: "".to_string(),
+                size: 0.,
+            });
+            log!("Install script for:", name, "failed", err);
+        }
+    }
+}
+
 fn run_process(entry: String, command: Vec<String>, status: Arc<Mutex<Option<GameStatus>>>) {
     match process::Command::new(entry).args(command).output() {
         Ok(output) => {
@@ -467,6 +551,7 @@ pub struct Client {
     receiver: Mutex<Receiver<String>>,
     sender: Mutex<Sender<Command>>,
     state: Arc<Mutex<State>>,
+    user: Mutex<String>,
 }
 
 impl Client {
@@ -479,6 +564,7 @@ impl Client {
             receiver: Mutex::new(rx1),
             sender: Mutex::new(tx2),
             state: Arc::new(Mutex::new(State::LoggedOut)),
+            user: Mutex::new(String::new()),
         };
         Client::start_process(client.state.clone(), tx1, rx2);
         client
@@ -500,11 +586,29 @@ impl Client {
         Ok(())
     }
 
+    /// Queues the game for removal with 'app_uninstall'.
+    pub fn uninstall(&self, game: &Game) -> Result<(), STError> {
+        let sender = self.sender.lock()?;
+        sender.send(Command::Uninstall(game.id, game.status_counter()))?;
+        Ok(())
+    }
+
     /// Quits previous SteamCmd instance, and spawns a new one. This can be useful for getting more
     /// state data. Old processes fail to update due to short comings in SteamCmd.
     pub fn restart(&self) -> Result<(), STError> {
+        let user = self.user.lock()?.clone();
+        let sender = self.sender.lock()?;
+        sender.send(Command::Restart(user))?;
+        Ok(())
+    }
+
+    /// Restarts SteamCmd logged in as another user, whose cached games `games` then returns.
+    pub fn switch_user(&self, user: &str) -> Result<(), STError> {
+        *self.user.lock()? = user.to_string();
+        let mut state = self.state.lock()?;
+        *state = State::LoggedOut;
         let sender = self.sender.lock()?;
-        sender.send(Command::Restart)?;
+        sender.send(Command::Restart(user.to_string()))?;
         Ok(())
     }
 
@@ -527,10 +631,30 @@ impl Client {
                 "Blank string. Requires user to log in.".to_string(),
             ));
         }
+        *self.user.lock()? = user.to_string();
+        let mut state = self.state.lock()?;
+        *state = State::LoggedOut;
+        let sender = self.sender.lock()?;
+        sender.send(Command::Login(user.to_string(), None, None))?;
+        Ok(())
+    }
+
+    /// Logs in again with whatever steamcmd prompted for, see `State::Prompt`.
+    pub fn login_with(
+        &self,
+        user: &str,
+        password: &str,
+        code: Option<&str>,
+    ) -> Result<(), STError> {
+        *self.user.lock()? = user.to_string();
         let mut state = self.state.lock()?;
         *state = State::LoggedOut;
         let sender = self.sender.lock()?;
-        sender.send(Command::Cli(format!("login {}", user)))?;
+        sender.send(Command::Login(
+            user.to_string(),
+            Some(password.to_string()),
+            code.map(str::to_string),
+        ))?;
         Ok(())
     }
 
@@ -551,9 +675,23 @@ impl Client {
         Ok(())
     }
 
+    /// Like `load_games`, but for a fixed list of apps rather than everything the account owns.
+    /// Anonymous logins don't own anything, so this is how they pick what to install.
+    pub fn load_apps(&self, ids: &[i32]) -> Result<(), STError> {
+        let mut state = self.state.lock()?;
+        *state = State::Loaded(0, -1);
+        let sender = self.sender.lock()?;
+        sender.send(Command::LoadApps(ids.to_vec()))?;
+        Ok(())
+    }
+
     /// Extracts games from cached location.
     pub fn games(&self) -> Result<Vec<Game>, STError> {
-        let db_content = fs::read_to_string(cache_location()?)?;
+        let user = self.user.lock()?.clone();
+        if user.is_empty() {
+            return Err(STError::Problem("No user to load games for.".to_string()));
+        }
+        let db_content = fs::read_to_string(cache_location(&user)?)?;
         let parsed: Vec<Game> = serde_json::from_str(&db_content)?;
         let mut processed: Vec<Game> = parsed
             .iter()
@@ -640,7 +778,71 @@ fn scrub_past_responses(mut cmd: SteamCmd, initial_response: String, scrub_respo
     return (cmd, response);
 }
 
+// steamcmd answers `app_info_print` for apps it hasn't fetched yet once it has, and for ids it
+// doesn't know not at all. Nudge it with blank lines while it's quiet until the info turns up or
+// time runs out. Every nudge gets a prompt back, those are all read here so the next command's
+// answer isn't one of them.
+fn await_app_info<C: Console>(
+    cmd: &mut C,
+    initial_response: String,
+    timeout: Duration,
+) -> Option<String> {
+    let deadline = Instant::now() + timeout;
+    let mut response = initial_response;
+    // Prompts still to come for blank lines sent.
+    let mut owed = nudge(cmd);
+    let found = loop {
+        if response.starts_with("[0mAppID") {
+            break Some(response);
+        }
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            log!("No app info in time", response);
+            break None;
+        }
+        match cmd.next_within(left.min(APP_INFO_POLL)) {
+            Some(buf) => {
+                response = String::from_utf8_lossy(&buf).into_owned();
+                if is_prompt(&response) {
+                    owed = owed.saturating_sub(1);
+                }
+            }
+            // Only nudge once the last ones are answered, so a silent steamcmd isn't flooded.
+            None if owed == 0 => owed = nudge(cmd),
+            None => {}
+        }
+    };
+    while owed > 0 {
+        match cmd.next_within(APP_INFO_POLL) {
+            Some(buf) if is_prompt(&String::from_utf8_lossy(&buf)) => owed -= 1,
+            Some(_) => {}
+            None => break,
+        }
+    }
+    found
+}
+
+// Blank lines sent, each good for a prompt.
+fn nudge<C: Console>(cmd: &mut C) -> usize {
+    (0..2).filter(|_| cmd.write("").is_ok()).count()
+}
+
+fn is_prompt(response: &str) -> bool {
+    response.ends_with("Steam>")
+}
+
 // Just some helpers broken out for testing
+// Loading progress once a command has loaded `updated` items and queued `queued` more, or
+// `None` when there's nothing left to load.
+fn progress(loaded: i32, total: i32, updated: i32, queued: i32) -> Option<(i32, i32)> {
+    let (loaded, total) = (loaded + updated, total + queued);
+    if loaded == total {
+        None
+    } else {
+        Some((loaded, total))
+    }
+}
+
 fn keys_from_licenses(licenses: String) -> Vec<i32> {
     licenses
         .lines()
@@ -656,11 +858,59 @@ fn keys_from_licenses(licenses: String) -> Vec<i32> {
 
 #[cfg(test)]
 mod tests {
-    use crate::client::{Client, Command, State};
+    use crate::client::{await_app_info, progress, Client, Command, State};
+    use crate::interface::steam_cmd::Console;
     use crate::util::error::STError;
+    use std::collections::VecDeque;
     use std::sync::mpsc::channel;
     use std::sync::Arc;
     use std::sync::Mutex;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    const PROMPT: &str = "[1m\nSteam>";
+    const APP_INFO: &str = "[0mAppID : 440, change number : 0/0";
+
+    // Stands in for steamcmd: answers blank lines with a prompt, unless it has gone quiet, and
+    // turns up the app info after a number of them if it's going to.
+    struct Fake {
+        quiet: bool,
+        info_after: Option<usize>,
+        writes: usize,
+        output: VecDeque<String>,
+    }
+
+    impl Fake {
+        fn new(quiet: bool, info_after: Option<usize>) -> Fake {
+            Fake {
+                quiet,
+                info_after,
+                writes: 0,
+                output: VecDeque::new(),
+            }
+        }
+    }
+
+    impl Console for Fake {
+        fn write(&mut self, _line: &str) -> Result<(), STError> {
+            self.writes += 1;
+            if self.info_after == Some(self.writes) {
+                self.output.push_back(APP_INFO.to_string());
+            }
+            if !self.quiet {
+                self.output.push_back(PROMPT.to_string());
+            }
+            Ok(())
+        }
+
+        fn next_within(&mut self, timeout: Duration) -> Option<Vec<u8>> {
+            let next = self.output.pop_front().map(String::into_bytes);
+            if next.is_none() {
+                thread::sleep(timeout);
+            }
+            next
+        }
+    }
 
     // Impure cases call to `steamcmd` which requires FHS.
     #[test]
@@ -693,6 +943,43 @@ mod tests {
             .contains(&"Command not found: doesn't".to_string()));
     }
 
+    #[test]
+    fn test_load_apps_progress() {
+        // Loading three apps starts at Loaded(0, 3), each `app_info_print` counts whether or not
+        // steamcmd knew the id.
+        assert_eq!(progress(0, 3, 1, 0), Some((1, 3)));
+        assert_eq!(progress(1, 3, 1, 0), Some((2, 3)));
+        assert_eq!(progress(2, 3, 1, 0), None);
+        // Packages queue up their apps as they're read.
+        assert_eq!(progress(0, 1, 1, 2), Some((1, 3)));
+        assert_eq!(progress(1, 3, 1, 0), Some((2, 3)));
+    }
+
+    #[test]
+    fn test_await_app_info() {
+        let timeout = Duration::from_millis(600);
+        // Unknown ids only ever get prompts back, and every one is read.
+        let mut unknown = Fake::new(false, None);
+        assert_eq!(await_app_info(&mut unknown, "[0m".into(), timeout), None);
+        assert!(unknown.writes > 2);
+        assert!(unknown.output.is_empty());
+
+        // A steamcmd that says nothing at all still gives up in time.
+        let mut silent = Fake::new(true, None);
+        let started = Instant::now();
+        assert_eq!(await_app_info(&mut silent, "[0m".into(), timeout), None);
+        assert!(started.elapsed() < timeout * 3);
+        assert_eq!(silent.writes, 2);
+
+        // Info that shows up late is still found, with the prompts before and after it read.
+        let mut late = Fake::new(false, Some(3));
+        assert_eq!(
+            await_app_info(&mut late, "[0m".into(), Duration::from_secs(10)),
+            Some(APP_INFO.to_string())
+        );
+        assert!(late.output.is_empty());
+    }
+
     #[test]
     fn test_blank_login() {
         let client = Client::new();
@@ -2,8 +2,16 @@ use crate::util::error::STError;
 use crate::util::paths::config_location;
 
 use crate::interface::game::GameType;
+use crate::interface::proton_data::PROTON_API;
+use crate::interface::steam_collections::SteamCollections;
+use crate::util::graphics::ImageMode;
+use crate::util::image::ArtworkKind;
+use crate::util::keymap::{default_bindings, Bindings};
+use crate::util::layout::PaneLayout;
+use crate::util::sort::SortOrder;
 
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 
 use tui::style::Color;
@@ -15,6 +23,120 @@ pub struct Config {
     pub favorite_games: Vec<i32>,
     pub allowed_games: Vec<GameType>,
     pub highlight: Color,
+    #[serde(default)]
+    pub sort: SortOrder,
+    // Unix timestamps of games launched through steam-tui.
+    #[serde(default)]
+    pub last_played: HashMap<i32, u64>,
+    #[serde(default)]
+    pub collections: BTreeMap<String, Vec<i32>>,
+    // Named search queries, shown alongside collections.
+    #[serde(default)]
+    pub saved_queries: BTreeMap<String, String>,
+    #[serde(default)]
+    pub artwork: ArtworkKind,
+    // Swap for a local mirror of Steam's CDN if you like.
+    #[serde(default = "default_cdn_url")]
+    pub cdn_url: String,
+    // Auto detects kitty, sixel or iTerm2 support, Off disables artwork altogether.
+    #[serde(default)]
+    pub image_mode: ImageMode,
+    #[serde(default)]
+    pub layout: PaneLayout,
+    // auto, dark, light or the name of a theme file.
+    #[serde(default = "default_theme")]
+    pub theme: String,
+    // Keys for each action, e.g. "Install": ["d", "ctrl+b"].
+    #[serde(default = "default_bindings")]
+    pub keymap: Bindings,
+    // Capturing the mouse stops the terminal from selecting text.
+    #[serde(default = "default_mouse")]
+    pub mouse: bool,
+    // Milliseconds between background refreshes, input and resizes redraw immediately.
+    #[serde(default = "default_tick_rate")]
+    pub tick_rate: u64,
+    // Leaves DLC out of the list, they still show in their game's details.
+    #[serde(default)]
+    pub hide_dlc: bool,
+    // Games with their DLC folded away in the list.
+    #[serde(default)]
+    pub collapsed_dlc: Vec<i32>,
+    // Base of ProtonDB's summary endpoint, each game is looked up at `<proton_url>/<id>.json`.
+    #[serde(default = "default_proton_url")]
+    pub proton_url: String,
+    // Hours before a cached ProtonDB summary is looked up again.
+    #[serde(default = "default_proton_ttl")]
+    pub proton_ttl: u64,
+    // Apps loaded for the anonymous login, which doesn't own anything.
+    #[serde(default)]
+    pub anonymous_apps: Vec<i32>,
+    // Every other saved account, the one in use lives in the fields above.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// What each account keeps to itself.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+#[serde(default)]
+pub struct Profile {
+    pub hidden_games: Vec<i32>,
+    pub favorite_games: Vec<i32>,
+    pub last_played: HashMap<i32, u64>,
+    pub collections: BTreeMap<String, Vec<i32>>,
+}
+
+fn default_cdn_url() -> String {
+    "https://steamcdn-a.akamaihd.net".to_string()
+}
+
+fn default_proton_url() -> String {
+    PROTON_API.to_string()
+}
+
+fn default_proton_ttl() -> u64 {
+    72
+}
+
+fn default_mouse() -> bool {
+    true
+}
+
+fn default_tick_rate() -> u64 {
+    500
+}
+
+fn default_theme() -> String {
+    "auto".to_string()
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            default_user: "".to_string(),
+            hidden_games: vec![],
+            favorite_games: vec![],
+            allowed_games: vec![GameType::Game, GameType::DLC],
+            highlight: Color::Green,
+            sort: SortOrder::default(),
+            last_played: HashMap::new(),
+            collections: BTreeMap::new(),
+            saved_queries: BTreeMap::new(),
+            artwork: ArtworkKind::default(),
+            cdn_url: default_cdn_url(),
+            image_mode: ImageMode::default(),
+            layout: PaneLayout::default(),
+            theme: default_theme(),
+            keymap: default_bindings(),
+            mouse: default_mouse(),
+            tick_rate: default_tick_rate(),
+            hide_dlc: false,
+            collapsed_dlc: vec![],
+            proton_url: default_proton_url(),
+            proton_ttl: default_proton_ttl(),
+            anonymous_apps: vec![],
+            profiles: BTreeMap::new(),
+        }
+    }
 }
 
 impl Config {
@@ -22,19 +144,104 @@ impl Config {
         match serde_json::from_str(&fs::read_to_string(config_location()?)?) {
             Ok(config) => Ok(config),
             _ => {
-                let config = Config {
-                    default_user: "".to_string(),
-                    hidden_games: vec![],
-                    favorite_games: vec![],
-                    allowed_games: vec![GameType::Game, GameType::DLC],
-                    highlight: Color::Green,
-                };
+                let config = Config::default();
                 config.save()?;
                 Ok(config)
             }
         }
     }
 
+    pub fn in_collection(&self, name: &str, id: i32) -> bool {
+        self.collections
+            .get(name)
+            .is_some_and(|ids| ids.contains(&id))
+    }
+
+    /// Adds the game to the named collection, or removes it if already present. Empty
+    /// collections are dropped.
+    pub fn toggle_collection(&mut self, name: &str, id: i32) {
+        let ids = self.collections.entry(name.to_string()).or_default();
+        if ids.contains(&id) {
+            ids.retain(|&x| x != id);
+        } else {
+            ids.push(id);
+        }
+        if ids.is_empty() {
+            self.collections.remove(name);
+        }
+    }
+
+    /// Merges what the Steam client knows into our own lists. Nothing is ever removed.
+    pub fn import(&mut self, steam: &SteamCollections) {
+        for id in &steam.favorites {
+            if !self.favorite_games.contains(id) {
+                self.favorite_games.push(*id);
+            }
+        }
+        for id in &steam.hidden {
+            if !self.hidden_games.contains(id) {
+                self.hidden_games.push(*id);
+            }
+        }
+        for (name, ids) in &steam.collections {
+            let collection = self.collections.entry(name.clone()).or_default();
+            for id in ids {
+                if !collection.contains(id) {
+                    collection.push(*id);
+                }
+            }
+        }
+    }
+
+    pub fn toggle_anonymous_app(&mut self, id: i32) {
+        if self.anonymous_apps.contains(&id) {
+            self.anonymous_apps.retain(|&x| x != id);
+        } else {
+            self.anonymous_apps.push(id);
+        }
+    }
+
+    /// Saved accounts in order, the one in use included.
+    pub fn accounts(&self) -> Vec<String> {
+        let mut accounts: Vec<String> = self.profiles.keys().cloned().collect();
+        if !self.default_user.is_empty() {
+            accounts.push(self.default_user.clone());
+        }
+        accounts.sort();
+        accounts
+    }
+
+    /// Puts the current account's lists away and brings out the given account's. A first
+    /// login keeps whatever was there, so older configs carry over.
+    pub fn switch_account(&mut self, user: &str) {
+        if user == self.default_user {
+            return;
+        }
+        let profile = self.profiles.remove(user);
+        if !self.default_user.is_empty() || profile.is_some() {
+            let current = Profile {
+                hidden_games: std::mem::take(&mut self.hidden_games),
+                favorite_games: std::mem::take(&mut self.favorite_games),
+                last_played: std::mem::take(&mut self.last_played),
+                collections: std::mem::take(&mut self.collections),
+            };
+            if !self.default_user.is_empty() {
+                self.profiles.insert(self.default_user.clone(), current);
+            }
+            let profile = profile.unwrap_or_default();
+            self.hidden_games = profile.hidden_games;
+            self.favorite_games = profile.favorite_games;
+            self.last_played = profile.last_played;
+            self.collections = profile.collections;
+        }
+        self.default_user = user.to_string();
+    }
+
+    /// Drops a saved account, the one in use stays.
+    pub fn forget_account(&mut self, user: &str) -> bool {
+        self.profiles.remove(user).is_some()
+    }
+
     pub fn save(&self) -> Result<(), STError> {
         Ok(fs::write(
             config_location()?,
@@ -42,3 +249,48 @@ impl Config {
         )?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+
+    #[test]
+    fn test_toggle_collection() {
+        let mut config = Config::default();
+        config.toggle_collection("co-op", 620);
+        config.toggle_collection("co-op", 440);
+        assert!(config.in_collection("co-op", 620));
+        assert_eq!(config.collections["co-op"], vec![620, 440]);
+
+        config.toggle_collection("co-op", 620);
+        assert!(!config.in_collection("co-op", 620));
+        // Emptied collections go away.
+        config.toggle_collection("co-op", 440);
+        assert!(config.collections.is_empty());
+    }
+
+    #[test]
+    fn test_switch_account() {
+        let mut config = Config {
+            favorite_games: vec![620],
+            ..Config::default()
+        };
+        // The first account keeps what was already there.
+        config.switch_account("gaben");
+        assert_eq!(config.favorite_games, vec![620]);
+
+        config.switch_account("alyx");
+        assert!(config.favorite_games.is_empty());
+        config.hidden_games = vec![440];
+        assert_eq!(config.accounts(), vec!["alyx", "gaben"]);
+
+        config.switch_account("gaben");
+        assert_eq!(config.favorite_games, vec![620]);
+        assert!(config.hidden_games.is_empty());
+        assert_eq!(config.profiles["alyx"].hidden_games, vec![440]);
+
+        assert!(config.forget_account("alyx"));
+        assert!(!config.forget_account("gaben"));
+        assert_eq!(config.accounts(), vec!["gaben"]);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::interface::anonymous::ANONYMOUS;
 use crate::util::{error::STError, parser::*};
 
 pub struct Account {
@@ -28,4 +29,13 @@ impl Account {
             _language: data[2].unwrap_or("").to_string(),
         })
     }
+
+    /// Anonymous logins have no account info to ask for.
+    pub fn anonymous() -> Account {
+        Account {
+            account: ANONYMOUS.to_string(),
+            _id: "".to_string(),
+            _language: "".to_string(),
+        }
+    }
 }
@@ -0,0 +1,43 @@
+/// Login that needs no account, enough for dedicated servers and SDK tools.
+pub const ANONYMOUS: &str = "anonymous";
+
+/// Popular apps anyone can download anonymously, offered in the picker.
+pub const WELL_KNOWN_APPS: &[(i32, &str)] = &[
+    (90, "Half-Life Dedicated Server"),
+    (1007, "Steamworks SDK Redist"),
+    (4020, "Garry's Mod Dedicated Server"),
+    (222860, "Left 4 Dead 2 Dedicated Server"),
+    (232250, "Team Fortress 2 Dedicated Server"),
+    (258550, "Rust Dedicated Server"),
+    (294420, "7 Days to Die Dedicated Server"),
+    (343050, "Don't Starve Together Dedicated Server"),
+    (380870, "Project Zomboid Dedicated Server"),
+    (896660, "Valheim Dedicated Server"),
+    (1110390, "Unturned Dedicated Server"),
+    (2394010, "Palworld Dedicated Server"),
+];
+
+/// Reads app ids typed as a comma or space separated list.
+pub fn parse_app_ids(input: &str) -> Result<Vec<i32>, String> {
+    input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|id| !id.is_empty())
+        .map(|id| match id.parse::<i32>() {
+            Ok(id) if id > 0 => Ok(id),
+            _ => Err(format!("'{}' isn't an app id", id)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interface::anonymous::parse_app_ids;
+
+    #[test]
+    fn test_parse_app_ids() {
+        assert_eq!(parse_app_ids("90, 740 896660"), Ok(vec![90, 740, 896660]));
+        assert_eq!(parse_app_ids(" "), Ok(vec![]));
+        assert!(parse_app_ids("90 valheim").is_err());
+        assert!(parse_app_ids("-5").is_err());
+    }
+}
@@ -1,13 +1,14 @@
-use std::thread;
-
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::Mutex;
 
 use crate::interface::game_status::GameStatus;
 
-use crate::interface::executable::Executable;
-use crate::interface::proton_data;
-use crate::util::{error::STError, parser::*, stateful::Named};
+use crate::interface::anonymous::ANONYMOUS;
+use crate::interface::executable::{Executable, Platform};
+use crate::interface::metadata::Metadata;
+use crate::interface::proton_data::{Proton, ProtonData};
+use crate::util::{error::STError, image::ArtworkKind, parser::*, query::Filter, stateful::Named};
 
 use crate::config::Config;
 use crate::util::log::log;
@@ -29,6 +30,17 @@ pub enum GameType {
     Tool,
     Unknown,
 }
+/// Artwork referenced by the app info. The icon is a hash, the rest are file names or languages
+/// that only tell us the asset exists.
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Assets {
+    pub icon: Option<String>,
+    pub header: Option<String>,
+    pub capsule: Option<String>,
+    pub hero: Option<String>,
+    pub logo: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Game {
     pub id: i32,
@@ -39,8 +51,20 @@ pub struct Game {
     pub executable: Vec<Executable>,
     pub game_type: GameType,
     pub icon_url: Option<String>,
+    #[serde(default)]
+    pub release_date: Option<u64>,
+    #[serde(default)]
+    pub assets: Assets,
+    // Base game of a DLC.
+    #[serde(default)]
+    pub parent: Option<i32>,
+    // Every DLC released for the game, owned or not.
+    #[serde(default)]
+    pub dlc: Vec<i32>,
+    #[serde(default)]
+    pub metadata: Metadata,
     #[serde(skip)]
-    proton_tier: Arc<Mutex<Option<String>>>,
+    proton: Arc<Mutex<Proton>>,
     #[serde(skip)]
     status: Arc<Mutex<Option<GameStatus>>>,
 }
@@ -95,7 +119,37 @@ impl Game {
                             }
                             _ => None,
                         },
-                        proton_tier: Arc::new(Mutex::new(None)),
+                        release_date: common
+                            .get("steam_release_date")
+                            .or_else(|| common.get("original_release_date"))
+                            .and_then(|date| date.maybe_value().ok())
+                            .and_then(|date| date.parse::<u64>().ok()),
+                        assets: Assets {
+                            icon: common.get("clienticon").and_then(|d| d.maybe_value().ok()),
+                            header: match common.get("header_image") {
+                                Some(Datum::Nest(header)) => header
+                                    .get("english")
+                                    .or_else(|| header.values().next())
+                                    .and_then(|d| d.maybe_value().ok()),
+                                _ => None,
+                            },
+                            capsule: library_asset(common, "library_capsule"),
+                            hero: library_asset(common, "library_hero"),
+                            logo: library_asset(common, "library_logo"),
+                        },
+                        parent: common
+                            .get("parent")
+                            .and_then(|parent| parent.maybe_value().ok())
+                            .and_then(|parent| parent.parse::<i32>().ok()),
+                        dlc: match extended.get("listofdlc") {
+                            Some(Datum::Value(list)) => list
+                                .split(',')
+                                .filter_map(|id| id.trim().parse::<i32>().ok())
+                                .collect(),
+                            _ => vec![],
+                        },
+                        metadata: Metadata::new(common),
+                        proton: Arc::new(Mutex::new(Proton::Unknown)),
                         status: Arc::new(Mutex::new(None)),
                     };
                     return Ok(game);
@@ -116,31 +170,60 @@ impl Game {
         Err(STError::Problem("Could not extract game.".to_string()))
     }
 
-    pub fn query_proton(&self) {
-        let guard = {
-            let mut tier = self.proton_tier.lock().unwrap();
-            if None == *tier {
-                *tier = Some("-".to_string());
-                true
-            } else {
-                false
-            }
-        };
-        if guard {
-            let reference = self.proton_tier.clone();
-            let id = self.id;
-            thread::spawn(move || {
-                if let Some(response) = proton_data::ProtonData::get(id) {
-                    let mut status = reference.lock().unwrap();
-                    *status = Some(response.format());
-                }
-            });
+    /// Where to fetch the given artwork from, relative to a Steam CDN (or a local mirror).
+    pub fn artwork_url(&self, kind: ArtworkKind, cdn: &str) -> Option<String> {
+        let cdn = cdn.trim_end_matches('/');
+        let store = |file: &str| format!("{}/steam/apps/{}/{}", cdn, self.id, file);
+        match kind {
+            ArtworkKind::Icon => match &self.assets.icon {
+                Some(hash) => Some(format!(
+                    "{}/steamcommunity/public/images/apps/{}/{}.ico",
+                    cdn, self.id, hash
+                )),
+                // Caches from before assets were tracked only have the full url.
+                None => self.icon_url.clone(),
+            },
+            ArtworkKind::Header => self.assets.header.as_deref().map(store),
+            ArtworkKind::Capsule => self
+                .assets
+                .capsule
+                .as_ref()
+                .map(|_| store("library_600x900.jpg")),
+            ArtworkKind::Hero => self.assets.hero.as_ref().map(|_| store("library_hero.jpg")),
+            ArtworkKind::Logo => self.assets.logo.as_ref().map(|_| store("logo.png")),
         }
     }
 
+    /// Shared with the ProtonDB worker, which fills it in.
+    pub fn proton_status(&self) -> Arc<Mutex<Proton>> {
+        self.proton.clone()
+    }
+
     pub fn get_proton(&self) -> String {
-        let status = self.proton_tier.lock().unwrap();
-        (*status).clone().unwrap_or_else(|| "-".to_string())
+        self.proton.lock().unwrap().format()
+    }
+
+    pub fn proton_summary(&self) -> Option<ProtonData> {
+        match &*self.proton.lock().unwrap() {
+            Proton::Ready(data) => Some(data.clone()),
+            _ => None,
+        }
+    }
+
+    /// Ships a Linux build, so Proton isn't needed.
+    pub fn is_native(&self) -> bool {
+        self.executable
+            .iter()
+            .any(|executable| executable.platform == Platform::Linux)
+    }
+
+    /// Proton tier rank, with native games above everything.
+    pub fn proton_rank(&self) -> u8 {
+        if self.is_native() {
+            ProtonData::rank("native")
+        } else {
+            ProtonData::rank(&self.get_proton())
+        }
     }
 
     pub fn get_status(&self) -> Option<GameStatus> {
@@ -148,6 +231,70 @@ impl Game {
         (*status).clone()
     }
 
+    /// Anything with a status that hasn't failed, been removed or yet to finish downloading
+    /// counts as installed.
+    pub fn is_installed(&self) -> bool {
+        match self.get_status() {
+            Some(status) => {
+                !(status.state == "uninstalled"
+                    || status.state.contains("Failed")
+                    || self.download_progress().is_some())
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.get_status()
+            .map(|status| status.state.starts_with("running"))
+            .unwrap_or(false)
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.get_status()
+            .map(|status| {
+                status.state.contains("Failed") || status.state == "Could not find entry program."
+            })
+            .unwrap_or(false)
+    }
+
+    pub fn is_dlc(&self) -> bool {
+        self.game_type == GameType::DLC
+    }
+
+    /// DLC for this game found among `games`, i.e. the owned ones.
+    pub fn owned_dlc<'a>(&self, games: &'a [Game]) -> Vec<&'a Game> {
+        games
+            .iter()
+            .filter(|game| {
+                game.id != self.id && (game.parent == Some(self.id) || self.dlc.contains(&game.id))
+            })
+            .collect()
+    }
+
+    /// steamcmd reports installs with a newer build available as needing an update.
+    pub fn needs_update(&self) -> bool {
+        self.get_status()
+            .map(|status| status.state.contains("Update Required"))
+            .unwrap_or(false)
+    }
+
+    /// Percent done while queued or downloading.
+    pub fn download_progress(&self) -> Option<f64> {
+        let state = self.get_status()?.state;
+        match state.as_str() {
+            "queued" | "processing..." => Some(0.),
+            _ => state
+                .strip_prefix("downloading ")
+                .and_then(|progress| progress.strip_suffix('%'))
+                .and_then(|progress| progress.parse::<f64>().ok()),
+        }
+    }
+
+    pub fn size(&self) -> f64 {
+        self.get_status().map(|status| status.size).unwrap_or(0.)
+    }
+
     pub fn update_status(self, new_status: GameStatus) {
         let mut status = self.status.lock().unwrap();
         *status = Some(new_status);
@@ -163,12 +310,40 @@ impl Game {
     pub fn status_counter(&self) -> Arc<Mutex<Option<GameStatus>>> {
         self.status.clone()
     }
+
+    /// Bare game for tests, with a status when given its state and size.
+    #[cfg(test)]
+    pub fn fixture(id: i32, name: &str, status: Option<(&str, f64)>) -> Game {
+        let game: Game = serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": name,
+            "developer": "-",
+            "homepage": "-",
+            "publisher": "-",
+            "executable": [],
+            "game_type": "Game",
+            "icon_url": null,
+        }))
+        .expect("Game");
+        let status = status.map(|(state, size)| GameStatus {
+            state: state.to_string(),
+            installdir: "/games".to_string(),
+            size,
+        });
+        Game::move_with_status(game, status)
+    }
+}
+
+fn library_asset(common: &HashMap<String, Datum>, key: &str) -> Option<String> {
+    match common.get("library_assets") {
+        // Newer app info nests per language images here, all we need is presence.
+        Some(Datum::Nest(assets)) => assets.get(key).map(|d| d.maybe_value().unwrap_or_default()),
+        _ => None,
+    }
 }
 
 impl Named for Game {
-    fn get_name(&self) -> String {
-        // Slow, and a hack- but whatever.
-        let config = Config::new().unwrap();
+    fn get_name(&self, config: &Config) -> String {
         if config.favorite_games.contains(&self.id) {
             format!("♡ {}", self.name.clone())
         } else {
@@ -176,9 +351,61 @@ impl Named for Game {
         }
     }
 
-    fn is_valid(&self) -> bool {
-        // Wooowww, I hate this. Could be worse though?
-        let config = Config::new().unwrap();
-        !&config.hidden_games.contains(&self.id) && config.allowed_games.contains(&self.game_type)
+    fn is_valid(&self, config: &Config) -> bool {
+        let folded = self.is_dlc()
+            && (config.hide_dlc
+                || self
+                    .parent
+                    .is_some_and(|parent| config.collapsed_dlc.contains(&parent)));
+        !&config.hidden_games.contains(&self.id)
+            // Apps picked for the anonymous login show whatever they are, but only there.
+            && (config.allowed_games.contains(&self.game_type)
+                || (config.default_user == ANONYMOUS && config.anonymous_apps.contains(&self.id)))
+            && !folded
+    }
+
+    fn matches(&self, filter: &Filter, config: &Config) -> bool {
+        match filter {
+            Filter::Name(name) => self.name.to_lowercase().contains(name),
+            Filter::Developer(developer) => self.developer.to_lowercase().contains(developer),
+            Filter::Publisher(publisher) => self.publisher.to_lowercase().contains(publisher),
+            Filter::Type(game_type) => &self.game_type == game_type,
+            Filter::Installed(installed) => self.is_installed() == *installed,
+            Filter::Proton(tier, true) => self.proton_rank() >= ProtonData::rank(tier),
+            Filter::Proton(tier, false) if tier == "native" => self.is_native(),
+            Filter::Proton(tier, false) => self.get_proton().to_lowercase().starts_with(tier),
+            Filter::Platform(platform) => self.executable.iter().any(|e| &e.platform == platform),
+            Filter::Size(..) => filter.size_matches(self.size()),
+            Filter::Favorite(favorite) => config.favorite_games.contains(&self.id) == *favorite,
+            Filter::Collection(name) => config
+                .collections
+                .iter()
+                .any(|(key, ids)| key.to_lowercase() == *name && ids.contains(&self.id)),
+            Filter::Downloading(downloading) => self.download_progress().is_some() == *downloading,
+            Filter::Update(update) => self.needs_update() == *update,
+            Filter::Played(played) => config.last_played.contains_key(&self.id) == *played,
+            Filter::Deck(category, true) => self.metadata.deck.category >= *category,
+            Filter::Deck(category, false) => self.metadata.deck.category == *category,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::interface::anonymous::ANONYMOUS;
+    use crate::interface::game::{Game, GameType};
+    use crate::util::stateful::Named;
+
+    #[test]
+    fn test_anonymous_apps_only_show_anonymously() {
+        let mut server = Game::fixture(90, "Half-Life Dedicated Server", None);
+        server.game_type = GameType::Tool;
+        let mut config = Config::default();
+        config.anonymous_apps.push(90);
+        config.default_user = "gaben".to_string();
+        assert!(!server.is_valid(&config));
+        config.default_user = ANONYMOUS.to_string();
+        assert!(server.is_valid(&config));
     }
 }
@@ -43,4 +43,50 @@ impl GameStatus {
             },
         }
     }
+
+    /// Status once `app_uninstall` has answered, either uninstalled or why it wasn't.
+    pub fn uninstall(maybe_status: &Option<GameStatus>, response: &str) -> GameStatus {
+        let lowered = response.to_lowercase();
+        if lowered.contains("error") || lowered.contains("fail") {
+            let reason = response
+                .lines()
+                .map(|line| line.trim_matches(|c: char| c.is_whitespace() || c == '\u{1b}'))
+                .rfind(|line| !line.is_empty())
+                .unwrap_or("app_uninstall failed");
+            GameStatus::msg(maybe_status, &format!("Failed: {}", reason))
+        } else {
+            GameStatus {
+                state: "uninstalled".to_string(),
+                installdir: "".to_string(),
+                size: 0.,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interface::game_status::GameStatus;
+
+    #[test]
+    fn test_uninstall() {
+        let installed = Some(GameStatus {
+            state: "uninstalling...".to_string(),
+            installdir: "/games/Portal".to_string(),
+            size: 42.,
+        });
+        let status = GameStatus::uninstall(&installed, "App '400' uninstalled.\n");
+        assert_eq!(status.state, "uninstalled");
+        assert_eq!(status.size, 0.);
+
+        let status = GameStatus::uninstall(
+            &installed,
+            "ERROR! Failed to uninstall app '400' (No Connection)\n",
+        );
+        assert_eq!(
+            status.state,
+            "Failed: ERROR! Failed to uninstall app '400' (No Connection)"
+        );
+        assert_eq!(status.installdir, "/games/Portal");
+    }
 }
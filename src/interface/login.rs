@@ -0,0 +1,232 @@
+/// Something steamcmd needs before it will log in.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Prompt {
+    Password,
+    // Emailed Steam Guard code.
+    GuardCode,
+    // Code from the Steam mobile authenticator.
+    TwoFactor,
+}
+
+impl Prompt {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Prompt::Password => "Password",
+            Prompt::GuardCode => "Steam Guard code (check your email)",
+            Prompt::TwoFactor => "Two-factor code (from the Steam mobile app)",
+        }
+    }
+
+    /// Whether typed input should be hidden.
+    pub fn is_secret(&self) -> bool {
+        *self == Prompt::Password
+    }
+}
+
+/// Why steamcmd refused to log in.
+#[derive(PartialEq, Debug, Clone)]
+pub enum LoginFailure {
+    RateLimited,
+    BadCode,
+    InvalidPassword,
+    // A quote or backslash, which steamcmd can't be given.
+    Unquotable,
+    Other(String),
+}
+
+impl LoginFailure {
+    pub fn message(&self) -> String {
+        match self {
+            LoginFailure::RateLimited => {
+                "Too many login attempts, wait a while before trying again".to_string()
+            }
+            LoginFailure::BadCode => "That code didn't work, try again".to_string(),
+            LoginFailure::InvalidPassword => "Invalid password".to_string(),
+            LoginFailure::Unquotable => {
+                "steamcmd can't take a \" or \\ here, log in with steamcmd itself to cache \
+                 your credentials"
+                    .to_string()
+            }
+            LoginFailure::Other(reason) => reason.clone(),
+        }
+    }
+}
+
+/// Where a login attempt ended up.
+#[derive(PartialEq, Debug, Clone)]
+pub enum LoginOutcome {
+    Success,
+    Prompt(Prompt),
+    Failure(LoginFailure),
+}
+
+impl LoginOutcome {
+    /// Reads a `login` response. Since password prompts are turned off, steamcmd fails with a
+    /// reason instead of asking, so the reason says what to prompt for.
+    pub fn parse(response: &str) -> LoginOutcome {
+        let lowered = response.to_lowercase();
+        let has = |needles: &[&str]| needles.iter().any(|needle| lowered.contains(needle));
+        if has(&["rate limit"]) {
+            LoginOutcome::Failure(LoginFailure::RateLimited)
+        } else if has(&["invalid password"]) {
+            LoginOutcome::Failure(LoginFailure::InvalidPassword)
+        } else if has(&["code mismatch", "invalid login auth code"]) {
+            LoginOutcome::Failure(LoginFailure::BadCode)
+        } else if has(&["two-factor", "two factor", "mobile authenticator"]) {
+            LoginOutcome::Prompt(Prompt::TwoFactor)
+        } else if has(&["steam guard", "logon denied", "verify email", "auth code"]) {
+            LoginOutcome::Prompt(Prompt::GuardCode)
+        } else if has(&["cached credentials", "password"]) {
+            LoginOutcome::Prompt(Prompt::Password)
+        } else if has(&["login failure", "failed"]) {
+            let reason = response
+                .lines()
+                .map(|line| line.trim_matches(|c: char| c.is_whitespace() || c == '\u{1b}'))
+                .rfind(|line| !line.is_empty())
+                .unwrap_or("Login failed")
+                .to_string();
+            LoginOutcome::Failure(LoginFailure::Other(reason))
+        } else {
+            LoginOutcome::Success
+        }
+    }
+
+    /// Being asked again for something that was just sent means it was wrong.
+    pub fn for_attempt(self, password: bool, code: bool) -> LoginOutcome {
+        match self {
+            LoginOutcome::Prompt(Prompt::Password) if password => {
+                LoginOutcome::Failure(LoginFailure::InvalidPassword)
+            }
+            LoginOutcome::Prompt(_) if code => LoginOutcome::Failure(LoginFailure::BadCode),
+            outcome => outcome,
+        }
+    }
+}
+
+/// The `login` line for steamcmd, each value quoted. steamcmd has no way to escape a quote, so
+/// values with quotes or backslashes are refused rather than sent changed, and control
+/// characters are dropped so nothing typed can end the line and run another command.
+pub fn login_command(
+    user: &str,
+    password: &Option<String>,
+    code: &Option<String>,
+) -> Result<String, LoginFailure> {
+    let mut line = "login".to_string();
+    for value in Some(user)
+        .into_iter()
+        .chain(password.iter().chain(code.iter()).map(String::as_str))
+    {
+        if value.contains(['"', '\\']) {
+            return Err(LoginFailure::Unquotable);
+        }
+        let value = value
+            .chars()
+            .filter(|c| !c.is_control())
+            .collect::<String>();
+        line.push_str(&format!(" \"{}\"", value));
+    }
+    Ok(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interface::login::{login_command, LoginFailure, LoginOutcome, Prompt};
+    use crate::util::parser::INPUT_LEX;
+
+    #[test]
+    fn test_parse_login() {
+        let cases = [
+            (
+                "[0mLogging in user 'gaben' to Steam Public...\nCached credentials not found.\nFAILED (No cached credentials and @NoPromptForPassword is set)",
+                LoginOutcome::Prompt(Prompt::Password),
+            ),
+            (
+                "Logging in user 'gaben' to Steam Public...FAILED (Invalid Password)",
+                LoginOutcome::Failure(LoginFailure::InvalidPassword),
+            ),
+            (
+                "Logging in user 'gaben' to Steam Public...FAILED (Account Logon Denied)",
+                LoginOutcome::Prompt(Prompt::GuardCode),
+            ),
+            (
+                "This account is protected by a Steam Guard mobile authenticator.\nFAILED (Two-factor code required)",
+                LoginOutcome::Prompt(Prompt::TwoFactor),
+            ),
+            (
+                "Logging in user 'gaben' to Steam Public...FAILED (Two-factor code mismatch)",
+                LoginOutcome::Failure(LoginFailure::BadCode),
+            ),
+            (
+                "Logging in user 'gaben' to Steam Public...FAILED (Rate Limit Exceeded)",
+                LoginOutcome::Failure(LoginFailure::RateLimited),
+            ),
+            (
+                "Logging in user 'gaben' to Steam Public...FAILED (Service Unavailable)\n",
+                LoginOutcome::Failure(LoginFailure::Other(
+                    "Logging in user 'gaben' to Steam Public...FAILED (Service Unavailable)"
+                        .to_string(),
+                )),
+            ),
+            (
+                "Logging in user 'gaben' to Steam Public...OK\nWaiting for user info...OK",
+                LoginOutcome::Success,
+            ),
+        ];
+        for (response, outcome) in cases.iter() {
+            assert_eq!(&LoginOutcome::parse(response), outcome, "{}", response);
+        }
+    }
+
+    #[test]
+    fn test_attempt() {
+        let password = LoginOutcome::Prompt(Prompt::Password);
+        let guard = LoginOutcome::Prompt(Prompt::GuardCode);
+        // Nothing was sent, so it's a genuine prompt.
+        assert_eq!(password.clone().for_attempt(false, false), password);
+        assert_eq!(guard.clone().for_attempt(false, false), guard);
+        assert_eq!(
+            password.clone().for_attempt(true, false),
+            LoginOutcome::Failure(LoginFailure::InvalidPassword)
+        );
+        // A right password moves on to asking for a code.
+        assert_eq!(guard.clone().for_attempt(true, false), guard);
+        assert_eq!(
+            guard.for_attempt(true, true),
+            LoginOutcome::Failure(LoginFailure::BadCode)
+        );
+        assert_eq!(
+            LoginOutcome::Success.for_attempt(true, true),
+            LoginOutcome::Success
+        );
+    }
+
+    #[test]
+    fn test_login_command() {
+        assert_eq!(
+            login_command("gaben", &None, &None),
+            Ok(r#"login "gaben""#.to_string())
+        );
+        // Line breaks can't sneak in another command.
+        assert_eq!(
+            login_command(
+                "gaben\nquit",
+                &Some("hl3 confirmed\nquit".to_string()),
+                &Some("ABC12".to_string())
+            ),
+            Ok(r#"login "gabenquit" "hl3 confirmedquit" "ABC12""#.to_string())
+        );
+        let refused = Err(LoginFailure::Unquotable);
+        assert_eq!(
+            login_command("gaben", &Some("say \"hi\"".to_string()), &None),
+            refused
+        );
+        assert_eq!(
+            login_command("gaben", &Some("\\o/".to_string()), &None),
+            refused
+        );
+        assert_eq!(login_command("gaben\"", &None, &None), refused);
+        // The client still knows the response is to a login.
+        let line = login_command("gaben", &None, &None).expect("Quotable");
+        assert_eq!(INPUT_LEX.tokenize(&line), ["login", "g"]);
+    }
+}
@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+
+use crate::util::parser::Datum;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct Language {
+    pub name: String,
+    pub full_audio: bool,
+}
+
+/// Valve's Steam Deck rating, worst to best.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub enum DeckCategory {
+    #[default]
+    Unknown,
+    Unsupported,
+    Playable,
+    Verified,
+}
+
+impl DeckCategory {
+    pub fn parse(category: &str) -> Option<DeckCategory> {
+        match category.to_lowercase().as_str() {
+            "unknown" | "untested" => Some(DeckCategory::Unknown),
+            "unsupported" => Some(DeckCategory::Unsupported),
+            "playable" => Some(DeckCategory::Playable),
+            "verified" => Some(DeckCategory::Verified),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DeckCategory::Unknown => "Unknown",
+            DeckCategory::Unsupported => "Unsupported",
+            DeckCategory::Playable => "Playable",
+            DeckCategory::Verified => "Verified",
+        }
+    }
+}
+
+/// One result from Valve's Deck testing, e.g. text being too small to read.
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct DeckTest {
+    pub passed: bool,
+    pub note: String,
+}
+
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Deck {
+    pub category: DeckCategory,
+    pub tests: Vec<DeckTest>,
+}
+
+impl Deck {
+    fn new(datum: Option<&Datum>) -> Deck {
+        let deck = match datum {
+            Some(Datum::Nest(deck)) => deck,
+            _ => return Deck::default(),
+        };
+        let category = match deck.get("category").and_then(|c| c.maybe_value().ok()) {
+            Some(category) => match category.as_str() {
+                "1" => DeckCategory::Unsupported,
+                "2" => DeckCategory::Playable,
+                "3" => DeckCategory::Verified,
+                _ => DeckCategory::Unknown,
+            },
+            None => DeckCategory::Unknown,
+        };
+        let mut tests = match deck.get("tests") {
+            Some(Datum::Nest(tests)) => tests
+                .iter()
+                .filter_map(|(key, test)| match test {
+                    Datum::Nest(test) => {
+                        let value = |key: &str| test.get(key).and_then(|d| d.maybe_value().ok());
+                        Some((
+                            key.parse::<u32>().unwrap_or(u32::MAX),
+                            DeckTest {
+                                // 4 is a pass, lower numbers are notes, warnings and failures.
+                                passed: value("display").as_deref() == Some("4"),
+                                note: deck_note(&value("token")?),
+                            },
+                        ))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<(u32, DeckTest)>>(),
+            _ => vec![],
+        };
+        tests.sort_by_key(|(i, _)| *i);
+        Deck {
+            category,
+            tests: tests.into_iter().map(|(_, test)| test).collect(),
+        }
+    }
+}
+
+// Tokens look like `#SteamDeckVerified_TestResult_TextInputHasSteamKeyboard`, so spell out the
+// last part as a sentence.
+fn deck_note(token: &str) -> String {
+    let name = token.rsplit('_').next().unwrap_or(token);
+    let mut note = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            note.push(' ');
+            note.extend(c.to_lowercase());
+        } else {
+            note.push(c);
+        }
+    }
+    note
+}
+
+/// Store details from the `common` section of the app info, shown in the extended details.
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Metadata {
+    pub genres: Vec<String>,
+    pub categories: Vec<String>,
+    pub languages: Vec<Language>,
+    pub controller_support: Option<String>,
+    pub metacritic: Option<u32>,
+    pub metacritic_url: Option<String>,
+    pub os_list: Vec<String>,
+    pub release_state: Option<String>,
+    pub deck: Deck,
+}
+
+impl Metadata {
+    pub fn new(common: &HashMap<String, Datum>) -> Metadata {
+        let value = |key: &str| common.get(key).and_then(|datum| datum.maybe_value().ok());
+        Metadata {
+            genres: numbered(common.get("genres"))
+                .into_iter()
+                .map(|id| genre(&id))
+                .collect(),
+            categories: numbered(common.get("category"))
+                .into_iter()
+                .map(|id| category(&id))
+                .collect(),
+            languages: languages(common),
+            controller_support: value("controller_support").map(|support| capitalize(&support)),
+            metacritic: value("metacritic_score").and_then(|score| score.parse::<u32>().ok()),
+            metacritic_url: value("metacritic_fullurl"),
+            os_list: value("oslist")
+                .map(|list| {
+                    list.split(',')
+                        .filter(|os| !os.is_empty())
+                        .map(|os| match os {
+                            "macos" => "macOS".to_string(),
+                            os => capitalize(os),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            release_state: value("releasestate").map(|state| capitalize(&state)),
+            deck: Deck::new(common.get("steam_deck_compatibility")),
+        }
+    }
+}
+
+// Genres are listed as `"0" "<id>"`, categories as `"category_<id>" "1"`. Either way the result
+// is the ids, in order.
+fn numbered(datum: Option<&Datum>) -> Vec<String> {
+    let mut entries = match datum {
+        Some(Datum::Nest(map)) => map
+            .iter()
+            .filter_map(
+                |(key, value)| match (key.strip_prefix("category_"), value) {
+                    (Some(id), _) => Some((id.parse::<u32>().unwrap_or(u32::MAX), id.to_string())),
+                    (None, Datum::Value(id)) => {
+                        Some((key.parse::<u32>().unwrap_or(u32::MAX), id.clone()))
+                    }
+                    _ => None,
+                },
+            )
+            .collect::<Vec<(u32, String)>>(),
+        _ => vec![],
+    };
+    entries.sort();
+    entries.into_iter().map(|(_, id)| id).collect()
+}
+
+fn languages(common: &HashMap<String, Datum>) -> Vec<Language> {
+    let mut languages = match common.get("supported_languages") {
+        // Newer app info says what each language supports.
+        Some(Datum::Nest(map)) => map
+            .iter()
+            .map(|(name, support)| Language {
+                name: language(name),
+                full_audio: match support {
+                    Datum::Nest(support) => {
+                        support.get("full_audio") == Some(&Datum::Value("true".to_string()))
+                    }
+                    _ => false,
+                },
+            })
+            .collect(),
+        _ => match common.get("languages") {
+            Some(Datum::Nest(map)) => map
+                .iter()
+                .filter(|(_, supported)| **supported == Datum::Value("1".to_string()))
+                .map(|(name, _)| Language {
+                    name: language(name),
+                    full_audio: false,
+                })
+                .collect(),
+            _ => vec![],
+        },
+    };
+    languages.sort_by(|a, b| a.name.cmp(&b.name));
+    languages
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn language(name: &str) -> String {
+    match name {
+        "schinese" => "Simplified Chinese".to_string(),
+        "tchinese" => "Traditional Chinese".to_string(),
+        "koreana" => "Korean".to_string(),
+        "brazilian" => "Portuguese (Brazil)".to_string(),
+        "latam" => "Spanish (Latin America)".to_string(),
+        name => capitalize(name),
+    }
+}
+
+// Names as shown on the store, the app info only has ids.
+fn genre(id: &str) -> String {
+    match id {
+        "1" => "Action",
+        "2" => "Strategy",
+        "3" => "RPG",
+        "4" => "Casual",
+        "9" => "Racing",
+        "18" => "Sports",
+        "23" => "Indie",
+        "25" => "Adventure",
+        "28" => "Simulation",
+        "29" => "Massively Multiplayer",
+        "37" => "Free to Play",
+        "51" => "Animation & Modeling",
+        "53" => "Design & Illustration",
+        "54" => "Education",
+        "55" => "Photo Editing",
+        "57" => "Utilities",
+        "58" => "Video Production",
+        "59" => "Web Publishing",
+        "60" => "Game Development",
+        "70" => "Early Access",
+        "71" => "Sexual Content",
+        "72" => "Nudity",
+        "73" => "Violent",
+        "74" => "Gore",
+        "81" => "Documentary",
+        "84" => "Tutorial",
+        id => return format!("Genre {}", id),
+    }
+    .to_string()
+}
+
+fn category(id: &str) -> String {
+    match id {
+        "1" => "Multi-player",
+        "2" => "Single-player",
+        "8" => "Valve Anti-Cheat",
+        "9" => "Co-op",
+        "13" => "Captions",
+        "14" => "Commentary",
+        "15" => "Stats",
+        "16" => "Includes Source SDK",
+        "17" => "Includes level editor",
+        "18" => "Partial controller support",
+        "20" => "MMO",
+        "21" => "Downloadable content",
+        "22" => "Steam Achievements",
+        "23" => "Steam Cloud",
+        "24" => "Shared/Split Screen",
+        "25" => "Steam Leaderboards",
+        "27" => "Cross-Platform Multiplayer",
+        "28" => "Full controller support",
+        "29" => "Steam Trading Cards",
+        "30" => "Steam Workshop",
+        "35" => "In-App Purchases",
+        "36" => "Online PvP",
+        "37" => "Shared/Split Screen PvP",
+        "38" => "Online Co-op",
+        "39" => "Shared/Split Screen Co-op",
+        "41" => "Remote Play on Phone",
+        "42" => "Remote Play on Tablet",
+        "43" => "Remote Play on TV",
+        "44" => "Remote Play Together",
+        "47" => "LAN PvP",
+        "48" => "LAN Co-op",
+        "49" => "PvP",
+        "61" => "HDR available",
+        "62" => "Family Sharing",
+        id => return format!("Category {}", id),
+    }
+    .to_string()
+}
+
+/// Formats a unix timestamp as a `YYYY-MM-DD` date (UTC).
+pub fn format_date(timestamp: u64) -> String {
+    // Days to civil date, from Howard Hinnant's date algorithms.
+    let z = (timestamp / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interface::metadata::{format_date, DeckCategory, Metadata};
+    use crate::util::parser::parse;
+
+    #[test]
+    fn test_parse_metadata() {
+        let mut block = r##"
+"common"
+{
+    "name" "Portal 2"
+    "oslist" "windows,macos,linux"
+    "controller_support" "full"
+    "metacritic_score" "95"
+    "releasestate" "released"
+    "genres"
+    {
+        "1" "25"
+        "0" "1"
+    }
+    "category"
+    {
+        "category_9" "1"
+        "category_2" "1"
+        "category_22" "1"
+    }
+    "supported_languages"
+    {
+        "english"
+        {
+            "supported" "true"
+            "full_audio" "true"
+        }
+        "schinese"
+        {
+            "supported" "true"
+        }
+    }
+    "steam_deck_compatibility"
+    {
+        "category" "2"
+        "tests"
+        {
+            "1"
+            {
+                "display" "3"
+                "token" "#SteamDeckVerified_TestResult_SomeTextTooSmall"
+            }
+            "0"
+            {
+                "display" "4"
+                "token" "#SteamDeckVerified_TestResult_DefaultControllerConfigFullySupported"
+            }
+        }
+    }
+}
+"##
+        .lines();
+        let map = parse(&mut block).maybe_nest().expect("Parsed");
+        let common = map.get("common").unwrap().maybe_nest().expect("Nested");
+        let metadata = Metadata::new(&common);
+        assert_eq!(metadata.genres, vec!["Action", "Adventure"]);
+        assert_eq!(
+            metadata.categories,
+            vec!["Single-player", "Co-op", "Steam Achievements"]
+        );
+        assert_eq!(metadata.os_list, vec!["Windows", "macOS", "Linux"]);
+        assert_eq!(metadata.controller_support, Some("Full".to_string()));
+        assert_eq!(metadata.metacritic, Some(95));
+        assert_eq!(metadata.languages.len(), 2);
+        assert_eq!(metadata.languages[0].name, "English");
+        assert!(metadata.languages[0].full_audio);
+        assert!(!metadata.languages[1].full_audio);
+
+        let deck = &metadata.deck;
+        assert_eq!(deck.category, DeckCategory::Playable);
+        assert_eq!(deck.tests.len(), 2);
+        assert!(deck.tests[0].passed);
+        assert_eq!(
+            deck.tests[0].note,
+            "Default controller config fully supported"
+        );
+        assert!(!deck.tests[1].passed);
+        assert_eq!(deck.tests[1].note, "Some text too small");
+        assert!(DeckCategory::Verified > DeckCategory::Playable);
+
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(1_303_257_600), "2011-04-20");
+    }
+}
@@ -1,6 +1,10 @@
 pub mod account;
+pub mod anonymous;
 pub mod executable;
 pub mod game;
 pub mod game_status;
+pub mod login;
+pub mod metadata;
 pub mod proton_data;
 pub mod steam_cmd;
+pub mod steam_collections;
@@ -1,30 +1,309 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::interface::game::Game;
+use crate::util::error::STError;
+use crate::util::log::log;
+use crate::util::paths::proton_cache_location;
+
 use serde::{Deserialize, Serialize};
 
 #[allow(non_snake_case)]
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct ProtonData {
-    confidence: String,
-    score: f32,
-    tier: String,
-    total: i32,
+    pub confidence: String,
+    pub score: f32,
+    pub tier: String,
+    pub total: i32,
 
     // We don't control the API. Pascal case isn't half bad though.
-    trendingTier: String,
-    bestReportedTier: String,
+    pub trendingTier: String,
+    pub bestReportedTier: String,
 }
 
-const PROTON_API: &str = "https://www.protondb.com/api/v1/reports/summaries";
+pub const PROTON_API: &str = "https://www.protondb.com/api/v1/reports/summaries";
+
+// Be nice to ProtonDB while prefetching a whole library.
+const PREFETCH_DELAY: Duration = Duration::from_millis(100);
 
 impl ProtonData {
-    pub fn get(id: i32) -> Option<Self> {
-        let url = format!("{}/{}.json", PROTON_API, id);
-        reqwest::blocking::get(url)
-            .map(|j| j.json::<ProtonData>().ok())
-            .ok()
-            .flatten()
+    /// Summary for the game, or `None` when ProtonDB has no reports for it.
+    pub fn get(url: &str, id: i32) -> Result<Option<Self>, STError> {
+        let url = format!("{}/{}.json", url.trim_end_matches('/'), id);
+        let response = reqwest::blocking::get(url)?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = response.error_for_status()?;
+        Ok(Some(response.json::<ProtonData>()?))
     }
 
-    pub fn format(self) -> String {
+    pub fn format(&self) -> String {
         format!("{} (score: {})", self.tier, self.score)
     }
+
+    /// Orders tiers from best to worst, works on formatted strings too.
+    pub fn rank(tier: &str) -> u8 {
+        match tier.split_whitespace().next().unwrap_or("") {
+            // Doesn't need Proton at all.
+            "native" => 6,
+            "platinum" => 5,
+            "gold" => 4,
+            "silver" => 3,
+            "bronze" => 2,
+            "borked" => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// What we know about a game on ProtonDB.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub enum Proton {
+    #[default]
+    Unknown,
+    Loading,
+    // ProtonDB has no reports.
+    Missing,
+    Failed(String),
+    Ready(ProtonData),
+}
+
+impl Proton {
+    pub fn format(&self) -> String {
+        match self {
+            Proton::Unknown | Proton::Missing => "-".to_string(),
+            Proton::Loading => "...".to_string(),
+            Proton::Failed(err) => format!("error: {}", err),
+            Proton::Ready(data) => data.format(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct Cached {
+    // Unix timestamp of the fetch.
+    fetched: u64,
+    summary: Option<ProtonData>,
+}
+
+/// Summaries kept on disk between runs, so tiers are there to sort and filter by right away.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ProtonCache {
+    entries: HashMap<i32, Cached>,
+}
+
+impl ProtonCache {
+    pub fn load() -> ProtonCache {
+        proton_cache_location()
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), STError> {
+        fs::write(proton_cache_location()?, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// The cached summary, unless it's older than the TTL.
+    pub fn fresh(&self, id: i32, now: u64, ttl: Duration) -> Option<Proton> {
+        self.entries
+            .get(&id)
+            .filter(|cached| now.saturating_sub(cached.fetched) < ttl.as_secs())
+            .map(|cached| match &cached.summary {
+                Some(data) => Proton::Ready(data.clone()),
+                None => Proton::Missing,
+            })
+    }
+
+    pub fn insert(&mut self, id: i32, now: u64, summary: Option<ProtonData>) {
+        self.entries.insert(
+            id,
+            Cached {
+                fetched: now,
+                summary,
+            },
+        );
+    }
+}
+
+struct Request {
+    id: i32,
+    status: Arc<Mutex<Proton>>,
+    // Selected games skip ahead of the prefetch queue.
+    urgent: bool,
+}
+
+/// Looks up ProtonDB summaries on a background thread, going to the network only for games
+/// missing from the cache or past the TTL.
+pub struct ProtonDb {
+    requests: Sender<Request>,
+    // Set once prefetched lookups drain, so tiers can be sorted again. Lookups for the selected
+    // game don't count, re-sorting for each of those would pull the list out from under the user.
+    settled: Arc<Mutex<bool>>,
+}
+
+impl ProtonDb {
+    pub fn new(url: &str, ttl: Duration) -> ProtonDb {
+        let (requests, receiver) = channel();
+        let settled = Arc::new(Mutex::new(false));
+        let reference = settled.clone();
+        let url = url.to_string();
+        thread::spawn(move || ProtonDb::work(receiver, reference, &url, ttl));
+        ProtonDb { requests, settled }
+    }
+
+    /// Whether prefetched lookups finished since last asked.
+    pub fn settled(&self) -> bool {
+        let mut settled = self.settled.lock().unwrap();
+        std::mem::replace(&mut *settled, false)
+    }
+
+    /// Looks the game up ahead of anything being prefetched.
+    pub fn request(&self, game: &Game) {
+        self.send(game, true);
+    }
+
+    pub fn prefetch(&self, games: &[Game]) {
+        for game in games {
+            self.send(game, false);
+        }
+    }
+
+    fn send(&self, game: &Game, urgent: bool) {
+        let status = game.proton_status();
+        {
+            let mut status = status.lock().unwrap();
+            match *status {
+                Proton::Unknown => *status = Proton::Loading,
+                // Coming back to a failed game tries again.
+                Proton::Failed(_) if urgent => *status = Proton::Loading,
+                // Already on its way, but may still jump the queue.
+                Proton::Loading if urgent => {}
+                _ => return,
+            }
+        }
+        let _ = self.requests.send(Request {
+            id: game.id,
+            status,
+            urgent,
+        });
+    }
+
+    fn work(receiver: Receiver<Request>, settled: Arc<Mutex<bool>>, url: &str, ttl: Duration) {
+        let mut cache = ProtonCache::load();
+        let mut queue = VecDeque::new();
+        let mut changed = false;
+        let mut dirty = false;
+        loop {
+            // Wait when there's nothing to do, then take everything that's waiting.
+            if queue.is_empty() {
+                if changed {
+                    *settled.lock().unwrap() = true;
+                    changed = false;
+                }
+                if dirty {
+                    if let Err(err) = cache.save() {
+                        log!("Failed to save ProtonDB cache", err);
+                    }
+                    dirty = false;
+                }
+                match receiver.recv() {
+                    Ok(request) => queue.push_back(request),
+                    Err(_) => return,
+                }
+            }
+            while let Ok(request) = receiver.try_recv() {
+                if request.urgent {
+                    queue.push_front(request);
+                } else {
+                    queue.push_back(request);
+                }
+            }
+            let request = match queue.pop_front() {
+                Some(request) => request,
+                None => continue,
+            };
+            if *request.status.lock().unwrap() != Proton::Loading {
+                continue;
+            }
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|now| now.as_secs())
+                .unwrap_or(0);
+            let proton = match cache.fresh(request.id, now, ttl) {
+                Some(proton) => proton,
+                None => {
+                    let fetched = ProtonData::get(url, request.id);
+                    if !request.urgent {
+                        thread::sleep(PREFETCH_DELAY);
+                    }
+                    match fetched {
+                        Ok(summary) => {
+                            cache.insert(request.id, now, summary.clone());
+                            dirty = true;
+                            match summary {
+                                Some(data) => Proton::Ready(data),
+                                None => Proton::Missing,
+                            }
+                        }
+                        Err(err) => {
+                            log!("ProtonDB lookup failed", request.id, err);
+                            Proton::Failed(match err {
+                                STError::Problem(problem) => problem,
+                                err => err.to_string(),
+                            })
+                        }
+                    }
+                }
+            };
+            *request.status.lock().unwrap() = proton;
+            changed |= !request.urgent;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interface::proton_data::{Proton, ProtonCache, ProtonData};
+    use crate::util::test_server::serve;
+    use std::time::Duration;
+
+    #[test]
+    fn test_fetch_from_configured_url() {
+        let url = serve(
+            "summaries",
+            vec![
+                (
+                    "200 OK",
+                    r#"{"confidence":"strong","score":0.9,"tier":"gold","total":42,"trendingTier":"platinum","bestReportedTier":"platinum"}"#,
+                ),
+                ("404 Not Found", ""),
+                ("500 Internal Server Error", ""),
+            ],
+        );
+        let data = ProtonData::get(&url, 620)
+            .expect("Fetched")
+            .expect("Has reports");
+        assert_eq!(data.tier, "gold");
+        assert_eq!(data.total, 42);
+        assert!(matches!(ProtonData::get(&url, 1), Ok(None)));
+        assert!(ProtonData::get(&url, 2).is_err());
+    }
+
+    #[test]
+    fn test_cache_expires() {
+        let mut cache = ProtonCache::default();
+        let ttl = Duration::from_secs(60);
+        cache.insert(620, 1000, None);
+        assert_eq!(cache.fresh(620, 1059, ttl), Some(Proton::Missing));
+        assert_eq!(cache.fresh(620, 1060, ttl), None);
+        assert_eq!(cache.fresh(440, 1000, ttl), None);
+    }
 }
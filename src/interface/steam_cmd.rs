@@ -2,12 +2,24 @@ use crate::util::error::STError;
 
 use std::process;
 
-use std::io::{BufRead, BufReader, Write};
+use std::io::{self, BufRead, BufReader, Write};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::Duration;
 
 use crate::util::log::log;
 
+/// Somewhere to send lines and read what comes back within a time limit. steamcmd is the only
+/// real one, tests stand in their own.
+pub trait Console {
+    fn write(&mut self, line: &str) -> Result<(), STError>;
+    /// The next piece of output, or `None` if nothing turned up in time.
+    fn next_within(&mut self, timeout: Duration) -> Option<Vec<u8>>;
+}
+
 pub struct SteamCmd {
-    iter: std::io::Split<BufReader<process::ChildStdout>>,
+    // Output is read on its own thread so waiting on it can time out.
+    output: Receiver<io::Result<Vec<u8>>>,
     stdin: process::ChildStdin,
 }
 
@@ -29,12 +41,19 @@ impl SteamCmd {
                 .stdout
                 .ok_or_else(|| STError::Problem("Failed to attach to stdout.".to_string()))?,
         );
-        let iter = f.split(sep);
+        let (sender, output) = channel();
+        thread::spawn(move || {
+            for piece in f.split(sep) {
+                if sender.send(piece).is_err() {
+                    break;
+                }
+            }
+        });
         let stdin = child
             .stdin
             .ok_or_else(|| STError::Problem("Failed to attach to stdin..".to_string()))?;
 
-        Ok(SteamCmd { iter, stdin })
+        Ok(SteamCmd { output, stdin })
     }
 
     fn with_args(args: Vec<&str>) -> Result<SteamCmd, STError> {
@@ -87,7 +106,20 @@ impl SteamCmd {
 impl Iterator for SteamCmd {
     type Item = Result<Vec<u8>, std::io::Error>;
     fn next(&mut self) -> Option<Result<Vec<u8>, std::io::Error>> {
-        self.iter.next()
+        self.output.recv().ok()
+    }
+}
+
+impl Console for SteamCmd {
+    fn write(&mut self, line: &str) -> Result<(), STError> {
+        SteamCmd::write(self, line)
+    }
+
+    fn next_within(&mut self, timeout: Duration) -> Option<Vec<u8>> {
+        match self.output.recv_timeout(timeout) {
+            Ok(Ok(piece)) => Some(piece),
+            _ => None,
+        }
     }
 }
 
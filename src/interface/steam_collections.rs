@@ -0,0 +1,289 @@
+use crate::util::{error::STError, log::log, parser::*, paths::steam_userdata_directory};
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+use serde_json::Value;
+
+/// Tags, favorites and hidden flags set in the Steam client itself.
+#[derive(PartialEq, Debug, Default)]
+pub struct SteamCollections {
+    pub favorites: Vec<i32>,
+    pub hidden: Vec<i32>,
+    pub collections: BTreeMap<String, Vec<i32>>,
+}
+
+impl SteamCollections {
+    /// Reads the user's sharedconfig.vdf and cloud storage namespaces. Other accounts signed in
+    /// to the Steam client on this machine have their own userdata and are left alone.
+    pub fn load(user: &str) -> Result<SteamCollections, STError> {
+        let userdata = steam_userdata_directory()?;
+        let logins = userdata.join("..").join("config").join("loginusers.vdf");
+        let id = fs::read_to_string(&logins)
+            .ok()
+            .and_then(|data| account_id(&data, user))
+            .ok_or_else(|| {
+                STError::Problem(format!("{} never signed in to the Steam client here", user))
+            })?;
+        let user = userdata.join(id.to_string());
+        let mut steam = SteamCollections::default();
+        let shared = user.join("7").join("remote").join("sharedconfig.vdf");
+        if let Ok(data) = fs::read_to_string(&shared) {
+            steam.merge(SteamCollections::from_sharedconfig(&data));
+        }
+        let cloud = user.join("config").join("cloudstorage");
+        if let Ok(entries) = fs::read_dir(cloud) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let is_namespace = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .map(|name| {
+                        name.starts_with("cloud-storage-namespace") && name.ends_with(".json")
+                    })
+                    .unwrap_or(false);
+                if !is_namespace {
+                    continue;
+                }
+                match fs::read_to_string(&path)
+                    .map_err(STError::from)
+                    .and_then(|data| SteamCollections::from_cloudstorage(&data))
+                {
+                    Ok(collections) => steam.merge(collections),
+                    Err(err) => log!("Skipping cloud storage", path, err),
+                }
+            }
+        }
+        Ok(steam)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.favorites.is_empty() && self.hidden.is_empty() && self.collections.is_empty()
+    }
+
+    /// Legacy format, tags live under each app as `"tags" { "0" "favorite" }`.
+    pub fn from_sharedconfig(data: &str) -> SteamCollections {
+        let mut steam = SteamCollections::default();
+        let root = parse(&mut data.lines());
+        let apps = ["UserRoamingConfigStore", "Software", "Valve", "Steam", "apps"]
+            .iter()
+            .try_fold(root, |datum, key| lookup(&datum, key));
+        if let Some(Datum::Nest(apps)) = apps {
+            for (id, app) in apps {
+                let id = match id.parse::<i32>() {
+                    Ok(id) => id,
+                    _ => continue,
+                };
+                if let Some(Datum::Value(hidden)) = lookup(&app, "hidden") {
+                    if hidden == "1" {
+                        steam.hidden.push(id);
+                    }
+                }
+                if let Some(Datum::Nest(tags)) = lookup(&app, "tags") {
+                    for tag in tags.values() {
+                        if let Datum::Value(tag) = tag {
+                            steam.tag(tag, id);
+                        }
+                    }
+                }
+            }
+        }
+        steam.normalize();
+        steam
+    }
+
+    /// Newer clients store collections as JSON blobs keyed `user-collections.<id>`.
+    pub fn from_cloudstorage(data: &str) -> Result<SteamCollections, STError> {
+        let mut steam = SteamCollections::default();
+        let entries: Vec<(String, HashMap<String, Value>)> = serde_json::from_str(data)?;
+        for (key, entry) in entries {
+            if !key.starts_with("user-collections.") {
+                continue;
+            }
+            if let Some(Value::Bool(true)) = entry.get("is_deleted") {
+                continue;
+            }
+            let value = match entry.get("value") {
+                Some(Value::String(value)) => serde_json::from_str::<Value>(value)?,
+                _ => continue,
+            };
+            let ids = |field: &str| -> Vec<i32> {
+                value
+                    .get(field)
+                    .and_then(Value::as_array)
+                    .map(|ids| {
+                        ids.iter()
+                            .filter_map(Value::as_i64)
+                            .map(|id| id as i32)
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            };
+            let removed = ids("removed");
+            let added = ids("added")
+                .into_iter()
+                .filter(|id| !removed.contains(id))
+                .collect::<Vec<i32>>();
+            // Dynamic collections only have a filter spec, nothing to import.
+            if added.is_empty() {
+                continue;
+            }
+            match value.get("id").and_then(Value::as_str) {
+                Some("favorite") => steam.favorites.extend(added),
+                Some("hidden") => steam.hidden.extend(added),
+                _ => {
+                    if let Some(name) = value.get("name").and_then(Value::as_str) {
+                        steam
+                            .collections
+                            .entry(name.to_string())
+                            .or_default()
+                            .extend(added);
+                    }
+                }
+            }
+        }
+        steam.normalize();
+        Ok(steam)
+    }
+
+    fn tag(&mut self, tag: &str, id: i32) {
+        if tag == "favorite" {
+            self.favorites.push(id);
+        } else {
+            self.collections.entry(tag.to_string()).or_default().push(id);
+        }
+    }
+
+    fn merge(&mut self, other: SteamCollections) {
+        self.favorites.extend(other.favorites);
+        self.hidden.extend(other.hidden);
+        for (name, ids) in other.collections {
+            self.collections.entry(name).or_default().extend(ids);
+        }
+        self.normalize();
+    }
+
+    fn normalize(&mut self) {
+        for ids in std::iter::once(&mut self.favorites)
+            .chain(std::iter::once(&mut self.hidden))
+            .chain(self.collections.values_mut())
+        {
+            ids.sort_unstable();
+            ids.dedup();
+        }
+    }
+}
+
+/// The account's userdata directory name, from the Steam client's loginusers.vdf. That file is
+/// keyed by 64 bit SteamID, the directory by the 32 bit account id within it.
+pub fn account_id(loginusers: &str, user: &str) -> Option<u64> {
+    match lookup(&parse(&mut loginusers.lines()), "users") {
+        Some(Datum::Nest(users)) => {
+            users
+                .iter()
+                .find_map(|(id, login)| match lookup(login, "AccountName") {
+                    Some(Datum::Value(name)) if name.eq_ignore_ascii_case(user) => {
+                        id.parse::<u64>().ok().map(|id| id & 0xFFFF_FFFF)
+                    }
+                    _ => None,
+                })
+        }
+        _ => None,
+    }
+}
+
+// Steam is inconsistent with casing ("apps" vs "Apps"), so look keys up loosely.
+fn lookup(datum: &Datum, key: &str) -> Option<Datum> {
+    match datum {
+        Datum::Nest(map) => map
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interface::steam_collections::{account_id, SteamCollections};
+
+    #[test]
+    fn test_parse_sharedconfig() {
+        let data = r#""UserRoamingConfigStore"
+{
+	"Software"
+	{
+		"Valve"
+		{
+			"Steam"
+			{
+				"Apps"
+				{
+					"440"
+					{
+						"tags"
+						{
+							"0"		"favorite"
+							"1"		"Co-op"
+						}
+					}
+					"620"
+					{
+						"tags"
+						{
+							"0"		"Co-op"
+						}
+						"Hidden"		"1"
+					}
+				}
+			}
+		}
+	}
+}
+"#;
+        let steam = SteamCollections::from_sharedconfig(data);
+        assert_eq!(steam.favorites, vec![440]);
+        assert_eq!(steam.hidden, vec![620]);
+        assert_eq!(steam.collections.get("Co-op"), Some(&vec![440, 620]));
+    }
+
+    #[test]
+    fn test_parse_cloudstorage() {
+        let data = r#"[
+            ["user-collections.favorite", {"key": "user-collections.favorite", "timestamp": 1,
+              "value": "{\"id\":\"favorite\",\"name\":\"\",\"added\":[440,70],\"removed\":[70]}"}],
+            ["user-collections.hidden", {"key": "user-collections.hidden", "timestamp": 1,
+              "value": "{\"id\":\"hidden\",\"name\":\"\",\"added\":[10],\"removed\":[]}"}],
+            ["user-collections.uc-abc", {"key": "user-collections.uc-abc", "timestamp": 1,
+              "value": "{\"id\":\"uc-abc\",\"name\":\"Backlog\",\"added\":[620,400],\"removed\":[]}"}],
+            ["user-collections.uc-gone", {"key": "user-collections.uc-gone", "timestamp": 1,
+              "is_deleted": true}],
+            ["showcases.1", {"key": "showcases.1", "timestamp": 1, "value": "{}"}]
+        ]"#;
+        let steam = SteamCollections::from_cloudstorage(data).expect("Valid cloud storage");
+        assert_eq!(steam.favorites, vec![440]);
+        assert_eq!(steam.hidden, vec![10]);
+        assert_eq!(steam.collections.len(), 1);
+        assert_eq!(steam.collections.get("Backlog"), Some(&vec![400, 620]));
+    }
+
+    #[test]
+    fn test_account_id() {
+        let data = r#""users"
+{
+	"76561197960287930"
+	{
+		"AccountName"		"gaben"
+		"PersonaName"		"Gabe"
+	}
+	"76561198000000001"
+	{
+		"AccountName"		"alyx"
+	}
+}
+"#;
+        assert_eq!(account_id(data, "gordon"), None);
+        assert_eq!(account_id(data, "GabeN"), Some(22202));
+        assert_eq!(account_id(data, "Alyx"), Some(39734273));
+    }
+}
@@ -1,32 +1,100 @@
 extern crate steam_tui;
 
 use std::io;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crossterm::event::KeyCode;
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::event::{KeyCode, MouseButton, MouseEventKind};
+use crossterm::execute;
 
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
 use tui::style::{Color, Style};
+use tui::widgets::Clear;
 use tui::{backend::CrosstermBackend, layout::Rect, Terminal};
 
 use terminal_light::background_color;
 
 use tui_image_rgba_updated::{ColorMode, Image};
 
-use steam_tui::util::event::{Event, Events};
-use steam_tui::util::image::update_img;
+use steam_tui::util::error::STError;
+use steam_tui::util::event::{self, Event, Events};
+use steam_tui::util::graphics::Graphics;
+use steam_tui::util::image::{scale, Artwork, ArtworkKind, Icon, ImageLoader};
+use steam_tui::util::keymap::Action;
+use steam_tui::util::layout::overlay;
+use steam_tui::util::paths::{invalidate_cache, migrate_cache};
+use steam_tui::util::sort::SortOrder;
 use steam_tui::util::stateful::StatefulList;
+use steam_tui::util::theme::Theme;
 
-use steam_tui::app::{App, Mode};
+use steam_tui::app::{
+    anonymous_apps, AccountEntry, App, Button, Collection, Mode, Pending, Regions,
+};
 use steam_tui::client::{Client, State};
 use steam_tui::config::Config;
+use steam_tui::interface::anonymous::{parse_app_ids, ANONYMOUS};
 use steam_tui::interface::game::Game;
+use steam_tui::interface::login::{LoginFailure, Prompt};
+use steam_tui::interface::proton_data::ProtonDb;
+use steam_tui::interface::steam_collections::SteamCollections;
 
-// why isn't this in stdlib for floats?
-fn min(a: f32, b: f32) -> f32 {
-    if a < b {
-        return a;
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+// Swaps in another saved account, showing its cached games while steamcmd logs in again.
+// Returns whether there was a cache to show.
+fn switch_account(
+    client: &Client,
+    game_list: &mut StatefulList<Game>,
+    app: &mut App,
+    config: &mut Config,
+    user: &str,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    config.switch_account(user);
+    config.save()?;
+    client.switch_user(user)?;
+    app.user = user.to_string();
+    app.mode = Mode::Loading;
+    app.clear_marks();
+    app.selections.clear();
+    app.collections.select(Some(0));
+    app.sidebar = !config.collections.is_empty() || !config.saved_queries.is_empty();
+    match client.games() {
+        Ok(games) => {
+            *game_list = StatefulList::with_items(games, config);
+            app.sort_games(game_list, config);
+            app.scope_games(game_list, config);
+            Ok(true)
+        }
+        _ => {
+            *game_list = StatefulList::new();
+            game_list.restart();
+            Ok(false)
+        }
+    }
+}
+
+// Opens the anonymous app picker.
+fn open_apps(app: &mut App, config: &Config) {
+    app.input.clear();
+    app.picked = config.anonymous_apps.clone();
+    app.apps.select(Some(0));
+    app.mode = Mode::Apps;
+}
+
+// Runs the selected game, remembering when for the last played sort.
+fn launch_selected(
+    client: &Client,
+    game_list: &mut StatefulList<Game>,
+    config: &mut Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(game) = game_list.selected() {
+        client.run(game)?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        config.last_played.insert(game.id, now);
+        Config::save(config)?;
+        game_list.set_config(config);
     }
-    b
+    Ok(())
 }
 
 fn entry() -> Result<(), Box<dyn std::error::Error>> {
@@ -36,25 +104,39 @@ fn entry() -> Result<(), Box<dyn std::error::Error>> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let terminal_bg = background_color()
+    let background = background_color()
         .map(|c| c.rgb())
-        .map(|c| Color::Rgb(c.r, c.g, c.b))
+        .map(|c| (c.r, c.g, c.b))
+        .ok();
+    let terminal_bg = background
+        .map(|(r, g, b)| Color::Rgb(r, g, b))
         .unwrap_or(Color::Gray);
 
+    let mut config = Config::new()?;
+    migrate_cache(&config.default_user)?;
+    let theme = Theme::from_config(&config, background);
+
     terminal.clear()?;
     terminal.draw(|frame| {
         let layout = App::build_layout();
         let placement = layout.split(frame.size());
-        frame.render_widget(App::build_splash(), placement[0]);
-        frame.render_widget(App::build_patience(), placement[1]);
+        frame.render_widget(App::build_splash(&theme), placement[0]);
+        frame.render_widget(App::build_patience(&theme), placement[1]);
     })?;
 
-    let mut img: Option<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>> = None;
+    // Keep a couple hundred decoded icons around, they're small.
+    let images = ImageLoader::new(256);
+    let protondb = ProtonDb::new(
+        &config.proton_url,
+        Duration::from_secs(config.proton_ttl.saturating_mul(60 * 60)),
+    );
 
     // Setup event handlers
-    let mut config = Config::new()?;
-    let mut app = App::new(&config);
-    let events = Events::new();
+    let mut app = App::new(&config, theme);
+    let events = Events::with_config(event::Config {
+        // Anything faster just burns CPU redrawing the same frame.
+        tick_rate: Duration::from_millis(config.tick_rate.max(50)),
+    });
     let client = Client::new();
 
     // Login before cache, otherwise metadata is empty.
@@ -67,256 +149,968 @@ fn entry() -> Result<(), Box<dyn std::error::Error>> {
     let mut cached: bool = false;
     match client.games() {
         Ok(games) => {
-            game_list = StatefulList::with_items(games);
+            game_list = StatefulList::with_items(games, &config);
+            app.sort_games(&mut game_list, &config);
+            app.scope_games(&mut game_list, &config);
+            protondb.prefetch(game_list.items());
             app.mode = Mode::Loading;
             cached = true;
         }
         _ => game_list.restart(),
     }
 
-    loop {
+    if config.mouse {
+        execute!(io::stdout(), EnableMouseCapture)?;
+    }
+    let mut last_click = None;
+
+    let mut graphics = Graphics::new(config.image_mode);
+    let mut shown = None;
+    // Half block artwork as last scaled, resizing large art every frame would stall input.
+    let mut scaled: Option<((i32, ArtworkKind, Rect), Icon)> = None;
+    let mut detailed = None;
+    'main: loop {
+        // Extended details start from the top for every game.
+        let selected = game_list.selected().map(|game| game.id);
+        if selected != detailed {
+            detailed = selected;
+            app.detail_scroll = 0;
+            if let Some(game) = game_list.selected() {
+                protondb.request(game);
+            }
+        }
+        // Selection changes all over the place, so fetch artwork once it lands somewhere new.
+        let selected = game_list.selected().map(|game| (game.id, config.artwork));
+        if selected != shown && graphics.protocol.is_some() {
+            shown = selected;
+            images.request(&game_list.selected(), config.artwork, &config.cdn_url);
+        }
+        // Artwork drawn with graphics escapes after tui is done with the frame.
+        let mut native = None;
         terminal.draw(|frame| {
             let layout = App::build_layout();
             let placement = layout.split(frame.size());
+            let theme = &app.theme;
             let help = match app.mode {
-                Mode::Normal => App::build_help(),
-                Mode::Terminated(_) => App::build_terminated_help(),
-                Mode::Login | Mode::Failed => App::build_login(app.user.clone()),
+                Mode::Normal | Mode::Searched if app.notice.is_some() => {
+                    App::build_notice(theme, app.notice.clone().unwrap_or_default())
+                }
+                Mode::Normal => App::build_help(theme, &app.keymap),
+                Mode::Terminated(_) => App::build_terminated_help(theme, &app.keymap),
+                Mode::Login | Mode::Failed => App::build_login(
+                    theme,
+                    app.prompt,
+                    match app.prompt {
+                        Some(_) => app.input.clone(),
+                        None => app.user.clone(),
+                    },
+                    app.login_error.clone(),
+                ),
                 Mode::Loading => match client.get_state() {
-                    Ok(State::Loaded(count, of)) => App::build_loaded(count, of),
-                    _ => App::build_loading(),
+                    Ok(State::Loaded(count, of)) => App::build_loaded(theme, count, of),
+                    _ => App::build_loading(theme),
                 },
-                Mode::Searching => App::build_query_searching(game_list.query.clone()),
-                Mode::Searched => App::build_query(game_list.query.clone()),
+                Mode::Searching => App::build_query_searching(theme, game_list.query().to_string()),
+                Mode::Searched => App::build_query(theme, game_list.query().to_string()),
+                Mode::Collecting => App::build_collecting(theme, app.input.clone(), &config),
+                Mode::Saving => App::build_saving(theme, app.input.clone()),
+                Mode::Confirming => App::build_confirming(theme, app.targets(&game_list).len()),
+                Mode::Finding => App::build_finding(theme, app.input.clone()),
+                Mode::Accounts => App::build_accounts(theme),
+                Mode::Apps => App::build_apps(theme, app.input.clone()),
             };
             match &app.mode {
-                Mode::Failed => frame.render_widget(App::build_splash_err(), placement[0]),
-                Mode::Terminated(err) => {
-                    frame.render_widget(App::build_splash_terminated(err.clone()), placement[0])
-                }
+                Mode::Failed => frame.render_widget(
+                    App::build_splash_err(theme, app.login_error.clone()),
+                    placement[0],
+                ),
+                Mode::Terminated(err) => frame.render_widget(
+                    App::build_splash_terminated(theme, err.clone()),
+                    placement[0],
+                ),
                 Mode::Loading | Mode::Login => {
-                    frame.render_widget(App::build_splash(), placement[0]);
+                    frame.render_widget(App::build_splash(theme), placement[0]);
                 }
+                Mode::Accounts => frame.render_stateful_widget(
+                    App::render_accounts(theme, &config),
+                    placement[0],
+                    &mut app.accounts,
+                ),
+                Mode::Apps => frame.render_stateful_widget(
+                    App::render_apps(theme, &config),
+                    placement[0],
+                    &mut app.apps,
+                ),
                 _ => {
-                    let game_layout = App::build_game_layout();
-                    let image_layout = App::build_image_layout();
-
-                    let (left, right) = App::render_games(app.highlight, &game_list);
-                    let game_placement = game_layout.split(placement[0]);
-                    // Incorrect image placement leads to hard crash. Explicitly calculate bounds
-                    // here.
-                    let image_placement = {
-                        let offset_x = game_placement[1].width + game_placement[1].x;
-                        let offset_y = game_placement[1].height + game_placement[1].y;
-                        let (width, height) = {
-                            // 62% is also hardcoded in the window width, and 160 is totally
-                            // arbitrary, but the really large images look super goofy.
-                            // TODO: Allow for user adjustable widths
-                            let width = min((offset_x as f32) * 0.62, 160.0);
-                            // Height is counted by row, and there are 10 lines of info.
-                            let height = min((offset_y as f32) - 10.0, 80.0);
-                            // Take minium, but respect aspect ratio.
-                            (
-                                min(width, height * 2.0) as u16,
-                                min(height, width / 2.0) as u16,
-                            )
-                        };
-                        image_layout.split(Rect {
-                            x: offset_x - width,
-                            y: offset_y - height,
-                            width,
-                            height,
-                        })
+                    let layout = &config.layout;
+                    let (left, right) = App::render_games(
+                        theme,
+                        &app.keymap,
+                        &game_list,
+                        app.sort_order(&config),
+                        &app.marked,
+                        app.anchor.is_some(),
+                    );
+                    let game_area = if app.sidebar {
+                        let sidebar_placement = App::build_sidebar_layout().split(placement[0]);
+                        frame.render_stateful_widget(
+                            App::render_collections(theme, &config),
+                            sidebar_placement[0],
+                            &mut app.collections,
+                        );
+                        sidebar_placement[1]
+                    } else {
+                        placement[0]
                     };
-
-                    frame.render_stateful_widget(left, game_placement[0], &mut game_list.state);
-                    frame.render_widget(right, game_placement[1]);
-                    if let Some(image) = img.clone() {
+                    let tabs_placement = App::build_tabs_layout().split(game_area);
+                    let (tabs, tab_areas) = App::render_tabs(theme, app.tab, tabs_placement[0]);
+                    frame.render_widget(tabs, tabs_placement[0]);
+                    let game_area = tabs_placement[1];
+                    // Narrow terminals give the list everything and show details on request.
+                    let (list_area, detail_area) = if layout.is_narrow(game_area.width) {
+                        (game_area, Some(overlay(game_area)).filter(|_| app.details))
+                    } else {
+                        let game_placement = App::build_game_layout(layout).split(game_area);
+                        (game_placement[0], Some(game_placement[1]))
+                    };
+                    frame.render_stateful_widget(left, list_area, &mut game_list.state);
+                    game_list.scroll(list_area.height.saturating_sub(2) as usize);
+                    let mut regions = Regions {
+                        list: list_area,
+                        tabs: tab_areas,
+                        ..Regions::default()
+                    };
+                    let extended = game_list.selected().filter(|_| app.extended);
+                    if let (Some(detail), Some(game)) = (detail_area, extended) {
+                        let lines = App::metadata_lines(theme, &app.keymap, game);
+                        app.detail_scroll =
+                            app.detail_scroll.min(lines.len().saturating_sub(1) as u16);
+                        frame.render_widget(Clear, detail);
                         frame.render_widget(
-                            Image::with_img(image)
-                                .color_mode(ColorMode::Rgba)
-                                .style(Style::default().bg(terminal_bg)),
-                            image_placement[0],
-                        )
+                            App::build_metadata(theme, &app.keymap, lines, app.detail_scroll),
+                            detail,
+                        );
+                        regions.detail = detail;
+                    } else if let Some(detail) = detail_area {
+                        frame.render_widget(Clear, detail);
+                        frame.render_widget(right, detail);
+                        regions.detail = detail;
+                        let artwork = match (graphics.protocol, game_list.selected()) {
+                            (Some(_), Some(game)) => images.get(&Some(game), config.artwork),
+                            _ => Artwork::Missing,
+                        };
+                        let mut rows = match game_list.selected() {
+                            Some(game) => layout.detail_height(
+                                game.get_status().is_some(),
+                                game.owned_dlc(game_list.items()).len(),
+                            ),
+                            None => layout.detail_height(false, 0),
+                        };
+                        // Buttons go on their own line after a gap below the details.
+                        if let Some(game) = game_list.selected() {
+                            let line = detail.y + rows + 2;
+                            if line + 1 < detail.y + detail.height {
+                                let area = Rect::new(
+                                    detail.x + 2,
+                                    line,
+                                    detail.width.saturating_sub(4),
+                                    1,
+                                );
+                                let (buttons, areas) = App::build_buttons(theme, game, area);
+                                frame.render_widget(buttons, area);
+                                regions.buttons = areas;
+                                rows += 2;
+                            }
+                        }
+                        let image_area = App::build_image_layout().split(layout.image_area(
+                            detail,
+                            rows,
+                            match &artwork {
+                                Artwork::Ready(image) => Some(image.as_ref()),
+                                _ => None,
+                            },
+                        ))[0];
+                        match artwork {
+                            _ if image_area.width == 0 || image_area.height == 0 => {}
+                            Artwork::Ready(image) if graphics.is_native() => {
+                                native = game_list
+                                    .selected()
+                                    .map(|game| ((game.id, config.artwork), image, image_area));
+                            }
+                            Artwork::Ready(image) => {
+                                let key = game_list
+                                    .selected()
+                                    .map(|game| (game.id, config.artwork, image_area));
+                                let icon = match (&scaled, key) {
+                                    (Some((last, icon)), Some(key)) if *last == key => icon.clone(),
+                                    _ => {
+                                        let icon =
+                                            scale(&image, image_area.width, image_area.height);
+                                        scaled = key.map(|key| (key, icon.clone()));
+                                        icon
+                                    }
+                                };
+                                frame.render_widget(
+                                    Image::with_img(icon)
+                                        .color_mode(ColorMode::Rgba)
+                                        .style(Style::default().bg(terminal_bg)),
+                                    image_area,
+                                )
+                            }
+                            Artwork::Loading => {
+                                frame.render_widget(App::build_placeholder(theme), image_area)
+                            }
+                            Artwork::Missing => {}
+                        }
                     }
+                    app.regions = regions;
                 }
             }
             frame.render_widget(help, placement[1]);
         })?;
+        match native {
+            Some((key, image, area)) => graphics.show(&mut io::stdout(), key, &image, area)?,
+            None => graphics.hide(&mut io::stdout())?,
+        }
 
-        if let Event::Input(input) = events.next()? {
-            match app.mode {
-                Mode::Terminated(_) => {
-                    if let KeyCode::Char('q') = input {
-                        break;
+        for event in events.batch()? {
+            if let Event::Interrupt = event {
+                break 'main;
+            }
+            if let Event::Resize = event {
+                // Images drawn with escapes don't move with the layout, so take them down and let
+                // the next frame place them again.
+                graphics.hide(&mut io::stdout())?;
+            }
+            if let Event::Tick = event {
+                // Tiers land in batches, only re-sort once they have so the list doesn't jump.
+                if protondb.settled() && app.sort_order(&config) == SortOrder::ProtonTier {
+                    let selected = game_list.selected().map(|game| game.id);
+                    app.sort_games(&mut game_list, &config);
+                    if let Some(id) = selected {
+                        game_list.select_by(|game| game.id == id);
                     }
                 }
-                Mode::Normal | Mode::Searched => match input {
-                    KeyCode::Char('l') => {
-                        app.mode = Mode::Login;
-                        terminal.show_cursor()?;
-                        game_list.restart();
-                    }
-                    KeyCode::Char('q') => {
-                        break;
-                    }
-                    KeyCode::Char('r') => {
-                        // Marked cache as false for the potential race condition
-                        // (you flush cache prior to login)
-                        cached = false;
-                        app.mode = Mode::Loading;
-                        client.restart()?;
-                    }
-                    KeyCode::Down | KeyCode::Char('j') | KeyCode::Char('s') => {
-                        game_list.next();
-                        img = update_img(&game_list.selected());
+                // Statuses and Proton tiers update in the background, so keep filters honest.
+                if game_list.has_filters() {
+                    game_list.refresh();
+                }
+            }
+            if let Event::Mouse(mouse) = event {
+                if let Mode::Normal | Mode::Searched = app.mode {
+                    let selected = game_list.state.selected();
+                    let details = app.extended && app.regions.in_detail(mouse.column, mouse.row);
+                    match mouse.kind {
+                        MouseEventKind::ScrollDown if details => {
+                            app.detail_scroll = app.detail_scroll.saturating_add(1);
+                        }
+                        MouseEventKind::ScrollUp if details => {
+                            app.detail_scroll = app.detail_scroll.saturating_sub(1);
+                        }
+                        MouseEventKind::ScrollDown => {
+                            game_list.select(selected.map_or(0, |i| i + 1))
+                        }
+                        MouseEventKind::ScrollUp => {
+                            game_list.select(selected.map_or(0, |i| i.saturating_sub(1)))
+                        }
+                        MouseEventKind::Down(MouseButton::Left) => {
+                            match app.regions.button(mouse.column, mouse.row) {
+                                Some(Button::Play) => {
+                                    launch_selected(&client, &mut game_list, &mut config)?
+                                }
+                                Some(Button::Install) | Some(Button::Verify) => {
+                                    if let Some(game) = game_list.selected() {
+                                        client.install(game)?;
+                                    }
+                                }
+                                None => {
+                                    if let Some(tab) = app.regions.tab(mouse.column, mouse.row) {
+                                        app.switch_tab(&mut game_list, &config, tab);
+                                    } else if let Some(i) = app
+                                        .regions
+                                        .list_row(mouse.column, mouse.row)
+                                        .and_then(|row| game_list.at_row(row))
+                                    {
+                                        let now = Instant::now();
+                                        let double = matches!(last_click, Some((at, j))
+                                            if j == i && now.duration_since(at) < DOUBLE_CLICK);
+                                        game_list.select(i);
+                                        if double {
+                                            launch_selected(&client, &mut game_list, &mut config)?;
+                                            last_click = None;
+                                        } else {
+                                            last_click = Some((now, i));
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        _ => {}
                     }
-                    KeyCode::Up | KeyCode::Char('k') | KeyCode::Char('w') => {
-                        game_list.previous();
-                        img = update_img(&game_list.selected());
+                }
+            }
+            if let Event::Input(input) = event {
+                app.notice = None;
+                match app.mode {
+                    Mode::Terminated(_) if app.keymap.action(input) == Some(Action::Quit) => {
+                        break 'main;
                     }
-                    KeyCode::Char('/') => {
-                        app.mode = Mode::Searching;
-                        terminal.show_cursor()?;
-                        game_list.unselect();
+                    Mode::Normal | Mode::Searched if app.pending == Some(Pending::Jump) => {
+                        app.pending = None;
+                        if let KeyCode::Char(letter) = input.code {
+                            game_list.jump_to(letter);
+                        }
                     }
-                    KeyCode::Char('\n') | KeyCode::Enter => {
-                        if let Some(game) = game_list.selected() {
-                            client.run(game)?;
+                    Mode::Normal | Mode::Searched
+                        if app.keymap.action(input).is_none()
+                            && matches!(input.code, KeyCode::Char(c) if c.is_ascii_digit()) =>
+                    {
+                        if let KeyCode::Char(c) = input.code {
+                            app.push_count(c.to_digit(10).unwrap_or(0));
                         }
                     }
-                    KeyCode::Char('f') => {
-                        if let Some(game) = game_list.selected() {
-                            if config.favorite_games.contains(&game.id) {
-                                config.favorite_games.retain(|&x| x != game.id);
-                            } else {
-                                config.favorite_games.push(game.id);
+                    Mode::Normal | Mode::Searched => match (
+                        app.keymap.action(input),
+                        app.count.take(),
+                        app.pending.take(),
+                    ) {
+                        (Some(Action::Top), count, Some(Pending::Top)) => {
+                            game_list.select(count.map_or(0, |n| n.saturating_sub(1)));
+                        }
+                        // Home goes straight to the top, `g` waits for a second `g`.
+                        (Some(Action::Top), count, _)
+                            if !matches!(input.code, KeyCode::Char(_)) =>
+                        {
+                            game_list.select(count.map_or(0, |n| n.saturating_sub(1)));
+                        }
+                        (Some(Action::Top), count, _) => {
+                            app.count = count;
+                            app.pending = Some(Pending::Top);
+                        }
+                        (Some(Action::Bottom), count, _) => {
+                            game_list.select(count.map_or(usize::MAX, |n| n.saturating_sub(1)));
+                        }
+                        (Some(action @ Action::PageDown), count, _)
+                        | (Some(action @ Action::PageUp), count, _)
+                        | (Some(action @ Action::HalfPageDown), count, _)
+                        | (Some(action @ Action::HalfPageUp), count, _) => {
+                            let rows = match action {
+                                Action::PageDown | Action::PageUp => game_list.rows(),
+                                _ => (game_list.rows() / 2).max(1),
+                            };
+                            let rows = (rows * count.unwrap_or(1)) as isize;
+                            game_list.move_by(match action {
+                                Action::PageDown | Action::HalfPageDown => rows,
+                                _ => -rows,
+                            });
+                        }
+                        (Some(action @ Action::NextTab), _, _)
+                        | (Some(action @ Action::PreviousTab), _, _) => {
+                            let tab = app.tab.cycle(action == Action::NextTab);
+                            app.switch_tab(&mut game_list, &config, tab);
+                        }
+                        (Some(Action::Extended), _, _) => {
+                            app.extended = !app.extended;
+                            // Narrow terminals need the overlay to show anything.
+                            app.details |= app.extended;
+                        }
+                        (Some(Action::ScrollDetailsDown), count, _) if app.extended => {
+                            let lines = count.unwrap_or(1).min(u16::MAX as usize) as u16;
+                            app.detail_scroll = app.detail_scroll.saturating_add(lines);
+                        }
+                        (Some(Action::ScrollDetailsUp), count, _) if app.extended => {
+                            let lines = count.unwrap_or(1).min(u16::MAX as usize) as u16;
+                            app.detail_scroll = app.detail_scroll.saturating_sub(lines);
+                        }
+                        (Some(Action::Fold), _, _) => {
+                            // Folding from a DLC folds its game and goes back up to it.
+                            let parent = game_list.selected().and_then(|game| match game.parent {
+                                Some(parent) if game.is_dlc() => Some(parent),
+                                _ if !game.owned_dlc(game_list.items()).is_empty() => Some(game.id),
+                                _ => None,
+                            });
+                            if let Some(parent) = parent {
+                                if config.collapsed_dlc.contains(&parent) {
+                                    config.collapsed_dlc.retain(|&id| id != parent);
+                                } else {
+                                    config.collapsed_dlc.push(parent);
+                                }
+                                Config::save(&config)?;
+                                game_list.set_config(&config);
+                                game_list.select_by(|game| game.id == parent);
                             }
+                        }
+                        (Some(Action::HideDlc), _, _) => {
+                            config.hide_dlc = !config.hide_dlc;
                             Config::save(&config)?;
+                            game_list.set_config(&config);
                         }
-                    }
-                    KeyCode::Char('F') => {
-                        // Hard refresh to restart games, since bad index can mess things up.
-                        game_list = StatefulList::with_items(client.games()?);
-                        game_list.query = "♡ ".to_string();
-                        app.mode = Mode::Searched;
-                    }
-                    KeyCode::Char('H') => {
-                        if let Some(game) = game_list.selected() {
-                            config.hidden_games.push(game.id);
+                        (Some(Action::Jump), _, _) => {
+                            app.pending = Some(Pending::Jump);
+                        }
+                        (Some(Action::Find), _, _) => {
+                            app.input.clear();
+                            app.mode = Mode::Finding;
+                            terminal.show_cursor()?;
+                        }
+                        (Some(action @ Action::NextMatch), count, _)
+                        | (Some(action @ Action::PreviousMatch), count, _)
+                            if !app.find.is_empty() =>
+                        {
+                            for _ in 0..count.unwrap_or(1) {
+                                game_list.find(&app.find, action == Action::NextMatch, false);
+                            }
+                        }
+                        // A single step wraps around, counted steps stop at the ends.
+                        (Some(Action::Down), None, _) => game_list.next(),
+                        (Some(Action::Up), None, _) => game_list.previous(),
+                        (Some(Action::Down), Some(count), _) => {
+                            game_list.move_by(count.min(isize::MAX as usize) as isize)
+                        }
+                        (Some(Action::Up), Some(count), _) => {
+                            game_list.move_by(-(count.min(isize::MAX as usize) as isize))
+                        }
+                        (Some(Action::Login), _, _) => {
+                            let current = AccountEntry::Saved(config.default_user.clone());
+                            let entries = AccountEntry::from_config(&config);
+                            app.accounts.select(Some(
+                                entries
+                                    .iter()
+                                    .position(|entry| *entry == current)
+                                    .unwrap_or(0),
+                            ));
+                            app.mode = Mode::Accounts;
+                        }
+                        (Some(Action::Apps), _, _) if config.default_user == ANONYMOUS => {
+                            open_apps(&mut app, &config);
+                        }
+                        (Some(Action::Quit), _, _) => {
+                            break 'main;
+                        }
+                        (Some(Action::Refresh), _, _) => {
+                            // Marked cache as false for the potential race condition
+                            // (you flush cache prior to login)
+                            cached = false;
+                            app.mode = Mode::Loading;
+                            client.restart()?;
+                        }
+                        (Some(Action::Search), _, _) => {
+                            app.mode = Mode::Searching;
+                            terminal.show_cursor()?;
+                            game_list.unselect();
+                        }
+                        (Some(Action::Launch), _, _) => {
+                            launch_selected(&client, &mut game_list, &mut config)?;
+                        }
+                        (Some(Action::Favorite), _, _) => {
+                            let targets = app
+                                .targets(&game_list)
+                                .iter()
+                                .map(|game| game.id)
+                                .collect::<Vec<i32>>();
+                            if !targets.is_empty() {
+                                // Mixed selections are all favorited, only all-favorites unfavorite.
+                                let all =
+                                    targets.iter().all(|id| config.favorite_games.contains(id));
+                                config.favorite_games.retain(|id| !targets.contains(id));
+                                if !all {
+                                    config.favorite_games.extend(targets);
+                                }
+                                Config::save(&config)?;
+                                game_list.set_config(&config);
+                            }
+                        }
+                        (Some(Action::ShowFavorites), _, _) => {
+                            game_list.set_query("fav:".to_string());
+                            game_list.restart();
+                            app.mode = Mode::Searched;
+                        }
+                        (Some(Action::Hide), _, _) => {
+                            let targets = app
+                                .targets(&game_list)
+                                .iter()
+                                .map(|game| game.id)
+                                .collect::<Vec<i32>>();
+                            if !targets.is_empty() {
+                                for id in targets {
+                                    if !config.hidden_games.contains(&id) {
+                                        config.hidden_games.push(id);
+                                    }
+                                }
+                                Config::save(&config)?;
+                                game_list.set_config(&config);
+                                game_list.previous();
+                                app.clear_marks();
+                            }
+                        }
+                        (Some(Action::Mark), _, _) => {
+                            if let Some(game) = game_list.selected() {
+                                app.toggle_mark(game.id);
+                                game_list.next();
+                            }
+                        }
+                        (Some(Action::Visual), _, _) => {
+                            app.visual(&game_list);
+                        }
+                        (Some(Action::Uninstall), _, _)
+                            if app
+                                .targets(&game_list)
+                                .iter()
+                                .any(|game| game.is_installed()) =>
+                        {
+                            app.mode = Mode::Confirming;
+                        }
+                        (Some(Action::Sort), _, _) => {
+                            config.sort = config.sort.next();
                             Config::save(&config)?;
-                            game_list.previous();
-                            img = update_img(&game_list.selected());
+                            game_list.set_config(&config);
+                            app.sort_games(&mut game_list, &config);
                         }
-                    }
-                    KeyCode::Char(' ') => {
-                        client.start_client()?;
-                    }
-                    KeyCode::Char('d') => {
-                        if let Some(game) = game_list.selected() {
-                            client.install(game)?;
+                        (Some(Action::Artwork), _, _) => {
+                            config.artwork = config.artwork.next();
+                            Config::save(&config)?;
+                            game_list.set_config(&config);
                         }
-                    }
-                    KeyCode::Esc => {
-                        app.mode = Mode::Normal;
-                        game_list.query = "".to_string();
-                    }
-                    _ => {}
-                },
-                Mode::Login | Mode::Failed => match input {
-                    KeyCode::Esc => {
-                        if client.is_logged_in()? {
-                            if game_list.query.is_empty() {
-                                app.mode = Mode::Normal;
+                        (Some(action @ Action::GrowList), _, _)
+                        | (Some(action @ Action::ShrinkList), _, _) => {
+                            config.layout.resize_list(if action == Action::GrowList {
+                                5
                             } else {
-                                app.mode = Mode::Searched;
+                                -5
+                            });
+                            Config::save(&config)?;
+                            game_list.set_config(&config);
+                        }
+                        (Some(action @ Action::GrowImage), _, _)
+                        | (Some(action @ Action::ShrinkImage), _, _) => {
+                            config
+                                .layout
+                                .resize_image(if action == Action::ShrinkImage {
+                                    -10
+                                } else {
+                                    10
+                                });
+                            Config::save(&config)?;
+                            game_list.set_config(&config);
+                        }
+                        (Some(Action::MoveImage), _, _) => {
+                            config.layout.image_position = config.layout.image_position.next();
+                            Config::save(&config)?;
+                            game_list.set_config(&config);
+                        }
+                        (Some(Action::Details), _, _) => {
+                            app.details = !app.details;
+                        }
+                        (Some(Action::Collect), _, _) if game_list.selected().is_some() => {
+                            app.input.clear();
+                            app.mode = Mode::Collecting;
+                            terminal.show_cursor()?;
+                        }
+                        (Some(Action::Sidebar), _, _) => {
+                            app.sidebar = !app.sidebar;
+                        }
+                        (Some(action @ Action::NextCollection), _, _)
+                        | (Some(action @ Action::PreviousCollection), _, _) => {
+                            app.cycle_collection(&config, action == Action::NextCollection);
+                            app.scope_games(&mut game_list, &config);
+                        }
+                        (Some(Action::SaveSearch), _, _) if app.mode == Mode::Searched => {
+                            app.input.clear();
+                            app.mode = Mode::Saving;
+                            terminal.show_cursor()?;
+                        }
+                        (Some(Action::RemoveCollection), _, _) => {
+                            let removed = match app.collection(&config) {
+                                Collection::Named(name) => {
+                                    config.collections.remove(&name).is_some()
+                                }
+                                Collection::Saved(name) => {
+                                    config.saved_queries.remove(&name).is_some()
+                                }
+                                _ => false,
+                            };
+                            if removed {
+                                Config::save(&config)?;
+                                app.collections.select(Some(0));
+                                app.scope_games(&mut game_list, &config);
                             }
-                            app.user = config.default_user.clone();
-                        } else {
-                            break;
                         }
-                    }
-                    KeyCode::Char('\n') | KeyCode::Enter => {
-                        let mut user = app.user.clone();
-                        user.retain(|c| !c.is_whitespace());
-                        terminal.hide_cursor()?;
-                        if !user.is_empty() {
+                        (Some(Action::Import), _, _) => {
+                            app.notice = Some(match SteamCollections::load(&config.default_user) {
+                                Ok(steam) if steam.is_empty() => {
+                                    "Nothing to import from the Steam client".to_string()
+                                }
+                                Ok(steam) => {
+                                    config.import(&steam);
+                                    Config::save(&config)?;
+                                    app.sidebar = true;
+                                    app.scope_games(&mut game_list, &config);
+                                    format!(
+                                        "Imported {} favorites, {} hidden games and {} collections",
+                                        steam.favorites.len(),
+                                        steam.hidden.len(),
+                                        steam.collections.len()
+                                    )
+                                }
+                                Err(STError::Problem(problem)) => {
+                                    format!("Import failed: {}", problem)
+                                }
+                                Err(err) => format!("Import failed: {}", err),
+                            });
+                        }
+                        (Some(Action::Steam), _, _) => {
+                            client.start_client()?;
+                        }
+                        (Some(Action::Install), _, _) => {
+                            // Installing validates too, so this also updates and verifies.
+                            for game in app.targets(&game_list) {
+                                client.install(game)?;
+                            }
+                        }
+                        (Some(Action::Back), _, _) if app.extended => {
+                            app.extended = false;
+                        }
+                        (Some(Action::Back), _, _) if app.has_marks() => {
+                            app.clear_marks();
+                        }
+                        (Some(Action::Back), _, _) => {
+                            app.mode = Mode::Normal;
+                            game_list.set_query("".to_string());
+                        }
+                        _ => {}
+                    },
+                    Mode::Login | Mode::Failed => match input.code {
+                        // Back out of a prompt to the username.
+                        KeyCode::Esc if app.prompt.is_some() => {
+                            app.prompt = None;
+                            app.password.clear();
+                            app.input.clear();
+                        }
+                        // Nothing typed yet.
+                        KeyCode::Char('\n') | KeyCode::Enter
+                            if app.prompt.is_some() && app.input.is_empty() => {}
+                        KeyCode::Char('\n') | KeyCode::Enter if app.prompt.is_some() => {
+                            let input = std::mem::take(&mut app.input);
+                            match app.prompt {
+                                Some(Prompt::Password) => {
+                                    app.password = input;
+                                    client.login_with(&config.default_user, &app.password, None)?;
+                                }
+                                _ => client.login_with(
+                                    &config.default_user,
+                                    &app.password,
+                                    Some(&input),
+                                )?,
+                            }
                             app.mode = Mode::Loading;
-                            config.default_user = user;
-                            client.login(&app.user)?;
+                            app.login_error = None;
+                            terminal.hide_cursor()?;
+                        }
+                        KeyCode::Backspace if app.prompt.is_some() => {
+                            app.input.pop();
+                        }
+                        KeyCode::Char(c) if app.prompt.is_some() => {
+                            app.input.push(c);
+                        }
+                        KeyCode::Esc => {
+                            if client.is_logged_in()? {
+                                if game_list.query().is_empty() {
+                                    app.mode = Mode::Normal;
+                                } else {
+                                    app.mode = Mode::Searched;
+                                }
+                                app.user = config.default_user.clone();
+                            } else {
+                                break 'main;
+                            }
+                        }
+                        KeyCode::Char('\n') | KeyCode::Enter => {
+                            let mut user = app.user.clone();
+                            user.retain(|c| !c.is_whitespace());
+                            terminal.hide_cursor()?;
+                            if user == config.default_user {
+                                app.mode = Mode::Loading;
+                                app.login_error = None;
+                                client.login(&user)?;
+                            } else if !user.is_empty() {
+                                app.login_error = None;
+                                cached = switch_account(
+                                    &client,
+                                    &mut game_list,
+                                    &mut app,
+                                    &mut config,
+                                    &user,
+                                )?;
+                                protondb.prefetch(game_list.items());
+                            }
+                        }
+                        KeyCode::Backspace => {
+                            app.user.pop();
+                        }
+                        KeyCode::Char(c) => {
+                            app.user.push(c);
+                        }
+                        _ => {}
+                    },
+                    Mode::Collecting | Mode::Saving => match input.code {
+                        KeyCode::Esc => {
+                            terminal.hide_cursor()?;
+                            app.browse(game_list.query());
+                        }
+                        KeyCode::Char('\n') | KeyCode::Enter => {
+                            let name = app.input.trim().to_string();
+                            if !name.is_empty() {
+                                if app.mode == Mode::Saving {
+                                    config
+                                        .saved_queries
+                                        .insert(name, game_list.query().to_string());
+                                } else {
+                                    let targets = app.targets(&game_list);
+                                    // Like favorites, only take games out when all are in.
+                                    let all = targets
+                                        .iter()
+                                        .all(|game| config.in_collection(&name, game.id));
+                                    for game in targets {
+                                        if config.in_collection(&name, game.id) == all {
+                                            config.toggle_collection(&name, game.id);
+                                        }
+                                    }
+                                }
+                                Config::save(&config)?;
+                                app.sidebar = true;
+                                app.scope_games(&mut game_list, &config);
+                            }
+                            terminal.hide_cursor()?;
+                            app.browse(game_list.query());
+                        }
+                        KeyCode::Backspace => {
+                            app.input.pop();
+                        }
+                        KeyCode::Char(c) => {
+                            app.input.push(c);
+                        }
+                        _ => {}
+                    },
+                    Mode::Finding => match input.code {
+                        KeyCode::Esc => {
+                            terminal.hide_cursor()?;
+                            app.browse(game_list.query());
+                        }
+                        KeyCode::Char('\n') | KeyCode::Enter => {
+                            app.find = app.input.trim().to_string();
+                            if !app.find.is_empty() {
+                                game_list.find(&app.find, true, true);
+                            }
+                            terminal.hide_cursor()?;
+                            app.browse(game_list.query());
+                        }
+                        KeyCode::Backspace => {
+                            app.input.pop();
+                        }
+                        KeyCode::Char(c) => {
+                            app.input.push(c);
+                        }
+                        _ => {}
+                    },
+                    Mode::Accounts => {
+                        let entries = AccountEntry::from_config(&config);
+                        let selected = app.accounts.selected().unwrap_or(0);
+                        match (app.keymap.action(input), input.code) {
+                            (_, KeyCode::Esc) => app.browse(game_list.query()),
+                            (_, KeyCode::Char('\n')) | (_, KeyCode::Enter) => {
+                                let user = match entries.get(selected) {
+                                    Some(AccountEntry::Saved(user)) => user.clone(),
+                                    Some(AccountEntry::Anonymous) => ANONYMOUS.to_string(),
+                                    _ => "".to_string(),
+                                };
+                                if user == config.default_user {
+                                    app.browse(game_list.query());
+                                } else if user.is_empty() {
+                                    app.user.clear();
+                                    app.mode = Mode::Login;
+                                    terminal.show_cursor()?;
+                                } else {
+                                    cached = switch_account(
+                                        &client,
+                                        &mut game_list,
+                                        &mut app,
+                                        &mut config,
+                                        &user,
+                                    )?;
+                                    protondb.prefetch(game_list.items());
+                                    terminal.clear()?;
+                                    graphics.invalidate();
+                                }
+                            }
+                            (_, KeyCode::Delete) => {
+                                if let Some(AccountEntry::Saved(user)) = entries.get(selected) {
+                                    if config.forget_account(user) {
+                                        config.save()?;
+                                        invalidate_cache(user)?;
+                                    }
+                                }
+                                let count = AccountEntry::from_config(&config).len();
+                                app.accounts
+                                    .select(Some(selected.min(count.saturating_sub(1))));
+                            }
+                            (Some(Action::Down), _) | (_, KeyCode::Down) => {
+                                app.accounts
+                                    .select(Some((selected + 1).min(entries.len() - 1)));
+                            }
+                            (Some(Action::Up), _) | (_, KeyCode::Up) => {
+                                app.accounts.select(Some(selected.saturating_sub(1)));
+                            }
+                            _ => {}
                         }
                     }
-                    KeyCode::Backspace => {
-                        app.user.pop();
-                    }
-                    KeyCode::Char(c) => {
-                        app.user.push(c);
-                    }
-                    _ => {}
-                },
-                Mode::Searching => match input {
-                    KeyCode::Esc => {
-                        app.mode = Mode::Normal;
-                        terminal.hide_cursor()?;
-                        game_list.query = "".to_string();
-                        img = update_img(&game_list.selected());
-                    }
-                    KeyCode::Char('\n') | KeyCode::Enter => {
-                        terminal.hide_cursor()?;
-                        app.mode = Mode::Searched;
-                    }
-                    KeyCode::Backspace => {
-                        game_list.query.pop();
-                        game_list.restart();
-                    }
-                    KeyCode::Char(c) => {
-                        game_list.query.push(c);
-                        game_list.restart();
-                        img = update_img(&game_list.selected());
-                    }
-                    KeyCode::Down => {
-                        game_list.next();
+                    Mode::Apps => {
+                        let apps = anonymous_apps(&config);
+                        let selected = app.apps.selected().unwrap_or(0);
+                        match input.code {
+                            KeyCode::Esc => {
+                                app.input.clear();
+                                if app.picked == config.anonymous_apps {
+                                    app.browse(game_list.query());
+                                } else {
+                                    config.save()?;
+                                    cached = false;
+                                    app.mode = Mode::Loading;
+                                    client.load_apps(&config.anonymous_apps)?;
+                                }
+                            }
+                            KeyCode::Char('\n') | KeyCode::Enter if app.input.is_empty() => {
+                                if let Some((id, _)) = apps.get(selected) {
+                                    config.toggle_anonymous_app(*id);
+                                }
+                            }
+                            KeyCode::Char('\n') | KeyCode::Enter => {
+                                // Ids that don't parse stay put to be fixed.
+                                if let Ok(ids) = parse_app_ids(&app.input) {
+                                    for id in ids {
+                                        if !config.anonymous_apps.contains(&id) {
+                                            config.anonymous_apps.push(id);
+                                        }
+                                    }
+                                    app.input.clear();
+                                }
+                            }
+                            KeyCode::Backspace => {
+                                app.input.pop();
+                            }
+                            KeyCode::Char(c) if c.is_ascii_digit() || c == ',' || c == ' ' => {
+                                app.input.push(c);
+                            }
+                            _ => match app.keymap.action(input) {
+                                Some(Action::Down) => {
+                                    app.apps.select(Some((selected + 1).min(apps.len() - 1)));
+                                }
+                                Some(Action::Up) => {
+                                    app.apps.select(Some(selected.saturating_sub(1)));
+                                }
+                                _ => {}
+                            },
+                        }
                     }
-                    KeyCode::Up => {
-                        game_list.previous();
+                    Mode::Confirming => {
+                        if input.code == KeyCode::Char('y') {
+                            for game in app.targets(&game_list) {
+                                if game.is_installed() {
+                                    client.uninstall(game)?;
+                                }
+                            }
+                            app.clear_marks();
+                        }
+                        app.browse(game_list.query());
                     }
+                    Mode::Searching => match input.code {
+                        KeyCode::Esc => {
+                            app.mode = Mode::Normal;
+                            terminal.hide_cursor()?;
+                            game_list.set_query("".to_string());
+                        }
+                        KeyCode::Char('\n') | KeyCode::Enter => {
+                            terminal.hide_cursor()?;
+                            app.mode = Mode::Searched;
+                        }
+                        KeyCode::Backspace => {
+                            game_list.pop_query();
+                            game_list.restart();
+                        }
+                        KeyCode::Char(c) => {
+                            game_list.push_query(c);
+                            game_list.restart();
+                        }
+                        KeyCode::Down => {
+                            game_list.next();
+                        }
+                        KeyCode::Up => {
+                            game_list.previous();
+                        }
+                        _ => {}
+                    },
                     _ => {}
-                },
-                _ => {}
-            }
-            // Need a hook to cancel if in loading mode.
-            if app.mode == Mode::Loading {
-                if let KeyCode::Char('q') = input {
-                    break;
+                }
+                // Need a hook to cancel if in loading mode.
+                if app.mode == Mode::Loading && app.keymap.action(input) == Some(Action::Quit) {
+                    break 'main;
                 }
             }
-            events.release();
         }
         if app.mode == Mode::Loading {
             match client.get_state()? {
                 State::Loaded(_, -2) => {
+                    // Logged in, so steamcmd has cached the credentials.
+                    app.prompt = None;
+                    app.password.clear();
                     // If loaded from cache then just used the cache
                     if cached {
                         // Importantly, mark cached as false to allow reloads
                         cached = false;
                         app.mode = Mode::Normal;
+                    } else if config.default_user == ANONYMOUS {
+                        client.load_apps(&config.anonymous_apps)?;
                     } else {
                         client.load_games()?;
                     }
                 }
                 State::LoggedIn => {
                     config.save()?;
-                    let query = game_list.query.clone();
+                    let query = game_list.query().to_string();
                     if query.is_empty() {
                         app.mode = Mode::Normal;
                     } else {
                         app.mode = Mode::Searched;
                     }
-                    game_list = StatefulList::with_items(client.games()?);
+                    game_list = StatefulList::with_items(client.games()?, &config);
+                    app.sort_games(&mut game_list, &config);
+                    app.scope_games(&mut game_list, &config);
+                    protondb.prefetch(game_list.items());
                     terminal.clear()?;
+                    graphics.invalidate();
+                    // Nothing to show until some apps are picked.
+                    if config.default_user == ANONYMOUS && config.anonymous_apps.is_empty() {
+                        open_apps(&mut app, &config);
+                    }
                 }
-                State::Failed => {
+                State::Prompt(prompt) => {
+                    app.mode = Mode::Login;
+                    app.prompt = Some(prompt);
+                    app.input.clear();
+                    terminal.show_cursor()?;
+                }
+                State::Failed(failure) => {
                     app.mode = Mode::Failed;
+                    app.login_error = Some(failure.message());
+                    // A wrong password or code can just be typed again, anything else starts over.
+                    match failure {
+                        LoginFailure::BadCode => {}
+                        LoginFailure::InvalidPassword => app.prompt = Some(Prompt::Password),
+                        _ => {
+                            app.prompt = None;
+                            app.password.clear();
+                        }
+                    }
+                    app.input.clear();
                     terminal.show_cursor()?;
                 }
                 _ => {}
@@ -326,6 +1120,10 @@ fn entry() -> Result<(), Box<dyn std::error::Error>> {
             app.mode = Mode::Terminated(err);
         }
     }
+    graphics.hide(&mut io::stdout())?;
+    if config.mouse {
+        execute!(io::stdout(), DisableMouseCapture)?;
+    }
     disable_raw_mode()?;
     terminal.clear()?;
     Ok(())
@@ -334,6 +1132,11 @@ fn entry() -> Result<(), Box<dyn std::error::Error>> {
 fn main() {
     match entry() {
         Ok(()) => {}
-        Err(err) => println!("{:?}", err),
+        Err(err) => {
+            // Errors skip the cleanup at the end of entry, so give the shell its terminal back.
+            let _ = execute!(io::stdout(), DisableMouseCapture);
+            let _ = disable_raw_mode();
+            println!("{:?}", err);
+        }
     }
 }
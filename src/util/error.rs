@@ -53,6 +53,12 @@ impl From<serde_json::Error> for STError {
     }
 }
 
+impl From<reqwest::Error> for STError {
+    fn from(err: reqwest::Error) -> STError {
+        STError::Problem(err.to_string())
+    }
+}
+
 impl From<Utf8Error> for STError {
     fn from(err: Utf8Error) -> STError {
         STError::Problem(format!("{:?}", err))
@@ -1,3 +1,4 @@
+use std::iter;
 use std::sync::mpsc;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
@@ -8,19 +9,29 @@ use std::time::Duration;
 
 use crate::util::log::log;
 
-use crossterm::event::{read, Event as CrossEvent, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{
+    read, Event as CrossEvent, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseEvent,
+    MouseEventKind,
+};
+
+// Most events handled between two frames, so a flood of input can't starve drawing.
+const BURST: usize = 64;
 
 pub enum Event<I> {
     Input(I),
+    Mouse(MouseEvent),
+    Resize,
     Tick,
+    // Ctrl-C quits whatever keys are bound, and whatever is being typed.
+    Interrupt,
 }
 
-/// A small event handler that wrap termion input and tick events. Each event
-/// type is handled in its own thread and returned to a common `Receiver`
+/// A small event handler that wraps crossterm input and tick events. Each event
+/// type is handled in its own thread and returned to a common `Receiver`. Every key is
+/// queued, so fast typing and held keys (which arrive as repeated presses) are never dropped.
 pub struct Events {
-    rx: mpsc::Receiver<Event<KeyCode>>,
+    rx: mpsc::Receiver<Event<KeyEvent>>,
     stop: Arc<AtomicBool>,
-    debounce: Arc<AtomicBool>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -42,42 +53,40 @@ impl Events {
     }
 
     pub fn with_config(config: Config) -> Events {
+        let terminal = iter::from_fn(|| match read() {
+            Ok(event) => Some(event),
+            Err(err) => {
+                log!("Input stopped", err);
+                None
+            }
+        });
+        Events::with_source(config, terminal)
+    }
+
+    /// Reads events from any source, e.g. a canned list in tests.
+    pub fn with_source<S>(config: Config, source: S) -> Events
+    where
+        S: IntoIterator<Item = CrossEvent>,
+        S::IntoIter: Send + 'static,
+    {
         let (tx, rx) = mpsc::channel();
         let stop = Arc::new(AtomicBool::new(false));
-        let debounce = Arc::new(AtomicBool::new(true));
 
         let _input_handle = {
             let tx = tx.clone();
             let stop = stop.clone();
-            let debounce = debounce.clone();
+            let source = source.into_iter();
             thread::spawn(move || {
-                //matching the key
-                loop {
-                    if let CrossEvent::Key(KeyEvent {
-                        code: kc,
-                        modifiers,
-                        ..
-                    }) = read().unwrap()
-                    {
-                        if debounce.load(Ordering::Relaxed) {
-                            // Let CTRL-c just be q
-                            let kc = if kc == KeyCode::Char('c')
-                                && modifiers.contains(KeyModifiers::CONTROL)
-                            {
-                                KeyCode::Char('q')
-                            } else {
-                                kc
-                            };
-                            if let Err(err) = tx.send(Event::Input(kc)) {
-                                log!(err);
-                                return;
-                            }
-                            debounce.store(false, Ordering::Relaxed);
-                        }
-                        if stop.load(Ordering::Relaxed) {
+                for event in source {
+                    if let Some(event) = translate(event) {
+                        if let Err(err) = tx.send(event) {
+                            log!(err);
                             return;
                         }
                     }
+                    if stop.load(Ordering::Relaxed) {
+                        return;
+                    }
                 }
             })
         };
@@ -93,15 +102,46 @@ impl Events {
                 }
             })
         };
-        Events { rx, stop, debounce }
+        Events { rx, stop }
     }
 
-    pub fn release(&self) {
-        self.debounce.store(true, Ordering::Relaxed);
+    pub fn next(&self) -> Result<Event<KeyEvent>, mpsc::RecvError> {
+        self.rx.recv()
     }
 
-    pub fn next(&self) -> Result<Event<KeyCode>, mpsc::RecvError> {
-        self.rx.recv()
+    /// Waits for an event, then takes whatever else is already queued so it can all be handled
+    /// before the next frame is drawn.
+    pub fn batch(&self) -> Result<Vec<Event<KeyEvent>>, mpsc::RecvError> {
+        let mut events = vec![self.next()?];
+        while events.len() < BURST {
+            match self.rx.try_recv() {
+                Ok(event) => events.push(event),
+                Err(_) => break,
+            }
+        }
+        Ok(events)
+    }
+}
+
+fn translate(event: CrossEvent) -> Option<Event<KeyEvent>> {
+    match event {
+        // Releases are only reported with keyboard enhancements, presses and repeats both act.
+        CrossEvent::Key(key) if key.kind == KeyEventKind::Release => None,
+        CrossEvent::Key(key)
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) =>
+        {
+            Some(Event::Interrupt)
+        }
+        CrossEvent::Key(key) => Some(Event::Input(key)),
+        // Motion is reported constantly with capture on, only clicks and the wheel matter.
+        CrossEvent::Mouse(mouse) => match mouse.kind {
+            MouseEventKind::Down(_) | MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                Some(Event::Mouse(mouse))
+            }
+            _ => None,
+        },
+        CrossEvent::Resize(_, _) => Some(Event::Resize),
+        _ => None,
     }
 }
 
@@ -116,3 +156,93 @@ impl Drop for Events {
         self.stop.store(true, Ordering::Relaxed);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::util::event::{Config, Event, Events};
+    use crossterm::event::{
+        Event as CrossEvent, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseEvent,
+        MouseEventKind,
+    };
+    use std::time::Duration;
+
+    fn key(c: char) -> CrossEvent {
+        CrossEvent::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE))
+    }
+
+    // Ticks are timing dependent, so only look at everything else.
+    fn drain(events: &Events, count: usize) -> Vec<Event<KeyEvent>> {
+        let mut received = Vec::new();
+        while received.len() < count {
+            for event in events.batch().expect("Events keep coming") {
+                if !matches!(event, Event::Tick) {
+                    received.push(event);
+                }
+            }
+        }
+        received
+    }
+
+    fn config() -> Config {
+        Config {
+            tick_rate: Duration::from_secs(3600),
+        }
+    }
+
+    #[test]
+    fn test_fast_typing_keeps_every_key() {
+        let source = "steam deck".chars().map(key).collect::<Vec<_>>();
+        let events = Events::with_source(config(), source);
+        let typed = drain(&events, 10)
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Input(KeyEvent {
+                    code: KeyCode::Char(c),
+                    ..
+                }) => Some(c),
+                _ => None,
+            })
+            .collect::<String>();
+        assert_eq!(typed, "steam deck");
+    }
+
+    #[test]
+    fn test_held_keys_and_noise() {
+        let mouse = |kind| {
+            CrossEvent::Mouse(MouseEvent {
+                kind,
+                column: 1,
+                row: 2,
+                modifiers: KeyModifiers::NONE,
+            })
+        };
+        let repeat = CrossEvent::Key(KeyEvent {
+            kind: KeyEventKind::Repeat,
+            ..KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE)
+        });
+        let release = CrossEvent::Key(KeyEvent {
+            kind: KeyEventKind::Release,
+            ..KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE)
+        });
+        let source = vec![
+            key('j'),
+            repeat.clone(),
+            repeat,
+            mouse(MouseEventKind::Moved),
+            release,
+            mouse(MouseEventKind::ScrollDown),
+            CrossEvent::Resize(80, 24),
+            CrossEvent::Key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+        ];
+        let events = Events::with_source(config(), source);
+        let received = drain(&events, 6);
+        let held = received
+            .iter()
+            .filter(|event| matches!(event, Event::Input(key) if key.code == KeyCode::Char('j')))
+            .count();
+        assert_eq!(held, 3);
+        assert!(matches!(received[3], Event::Mouse(_)));
+        assert!(matches!(received[4], Event::Resize));
+        assert!(matches!(received[5], Event::Interrupt));
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, Cursor, Write};
+
+use crate::util::image::{ArtworkKind, Icon};
+
+use image::imageops::{resize, FilterType};
+use image::{DynamicImage, ImageOutputFormat};
+use serde::{Deserialize, Serialize};
+use tui::layout::Rect;
+
+// Cell size in pixels isn't knowable without querying the terminal, so assume a common one for
+// sixel, which needs pixels. Kitty and iTerm2 scale to cells themselves.
+const CELL_WIDTH: u32 = 8;
+const CELL_HEIGHT: u32 = 16;
+// Kitty limits escape payloads to 4096 bytes.
+const KITTY_CHUNK: usize = 4096;
+
+/// How artwork should be drawn. `Auto` picks the best protocol the terminal seems to support.
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub enum ImageMode {
+    #[default]
+    Auto,
+    Kitty,
+    Sixel,
+    Iterm,
+    Blocks,
+    Off,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Protocol {
+    Kitty,
+    Sixel,
+    Iterm,
+    Blocks,
+}
+
+impl ImageMode {
+    pub fn resolve(&self) -> Option<Protocol> {
+        match self {
+            ImageMode::Auto => Some(Protocol::detect()),
+            ImageMode::Kitty => Some(Protocol::Kitty),
+            ImageMode::Sixel => Some(Protocol::Sixel),
+            ImageMode::Iterm => Some(Protocol::Iterm),
+            ImageMode::Blocks => Some(Protocol::Blocks),
+            ImageMode::Off => None,
+        }
+    }
+}
+
+impl Protocol {
+    /// Best guess from the environment. Multiplexers swallow graphics escapes, so fall back to
+    /// blocks there unless a mode is forced in the config.
+    pub fn detect() -> Protocol {
+        let var = |key: &str| env::var(key).unwrap_or_default();
+        let (term, program) = (var("TERM"), var("TERM_PROGRAM"));
+        if env::var("TMUX").is_ok() || term.starts_with("screen") {
+            Protocol::Blocks
+        } else if env::var("KITTY_WINDOW_ID").is_ok()
+            || term.contains("kitty")
+            || program == "ghostty"
+        {
+            Protocol::Kitty
+        } else if program == "iTerm.app" || program == "WezTerm" || var("LC_TERMINAL") == "iTerm2"
+        {
+            Protocol::Iterm
+        } else if term.contains("sixel")
+            || term.starts_with("foot")
+            || term.starts_with("mlterm")
+            || term.starts_with("contour")
+        {
+            Protocol::Sixel
+        } else {
+            Protocol::Blocks
+        }
+    }
+}
+
+/// Draws artwork with terminal graphics escapes, outside of tui's buffer. Escapes are only
+/// written when the artwork or its placement changes.
+pub struct Graphics {
+    pub protocol: Option<Protocol>,
+    shown: Option<(i32, ArtworkKind, Rect)>,
+}
+
+impl Graphics {
+    pub fn new(mode: ImageMode) -> Graphics {
+        Graphics {
+            protocol: mode.resolve(),
+            shown: None,
+        }
+    }
+
+    /// Whether images are drawn with escapes rather than half blocks in the buffer.
+    pub fn is_native(&self) -> bool {
+        !matches!(self.protocol, None | Some(Protocol::Blocks))
+    }
+
+    /// Forget what's on screen, e.g. after the terminal has been cleared.
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+    }
+
+    pub fn show<W: Write>(
+        &mut self,
+        out: &mut W,
+        key: (i32, ArtworkKind),
+        image: &Icon,
+        area: Rect,
+    ) -> io::Result<()> {
+        let shown = Some((key.0, key.1, area));
+        if !self.is_native() || self.shown == shown {
+            return Ok(());
+        }
+        self.hide(out)?;
+        let payload = match self.protocol {
+            Some(Protocol::Kitty) => kitty(image, area),
+            Some(Protocol::Iterm) => iterm(image, area),
+            Some(Protocol::Sixel) => sixel(&resize(
+                image,
+                area.width as u32 * CELL_WIDTH,
+                area.height as u32 * CELL_HEIGHT,
+                FilterType::Triangle,
+            )),
+            _ => return Ok(()),
+        };
+        // Escapes draw from the cursor, so save it and move to the top left of the area.
+        write!(out, "\x1b7\x1b[{};{}H", area.y + 1, area.x + 1)?;
+        out.write_all(payload.as_bytes())?;
+        write!(out, "\x1b8")?;
+        out.flush()?;
+        self.shown = shown;
+        Ok(())
+    }
+
+    pub fn hide<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        if let Some((_, _, area)) = self.shown.take() {
+            match self.protocol {
+                Some(Protocol::Kitty) => write!(out, "\x1b_Ga=d,d=A,q=2\x1b\\")?,
+                // Pixels stick around until overwritten, and tui thinks these cells are blank.
+                _ => {
+                    for row in area.y..area.y + area.height {
+                        write!(out, "\x1b7\x1b[{};{}H", row + 1, area.x + 1)?;
+                        write!(out, "{}\x1b8", " ".repeat(area.width as usize))?;
+                    }
+                }
+            }
+            out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn png(image: &Icon) -> Vec<u8> {
+    let mut bytes = Cursor::new(Vec::new());
+    let _ = DynamicImage::ImageRgba8(image.clone()).write_to(&mut bytes, ImageOutputFormat::Png);
+    bytes.into_inner()
+}
+
+fn kitty(image: &Icon, area: Rect) -> String {
+    let data = base64::encode(png(image));
+    let chunks = data.as_bytes().chunks(KITTY_CHUNK).collect::<Vec<_>>();
+    let mut payload = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = if i + 1 < chunks.len() { 1 } else { 0 };
+        let chunk = std::str::from_utf8(chunk).unwrap_or("");
+        if i == 0 {
+            payload.push_str(&format!(
+                "\x1b_Ga=T,f=100,q=2,C=1,c={},r={},m={};{}\x1b\\",
+                area.width, area.height, more, chunk
+            ));
+        } else {
+            payload.push_str(&format!("\x1b_Gm={};{}\x1b\\", more, chunk));
+        }
+    }
+    payload
+}
+
+fn iterm(image: &Icon, area: Rect) -> String {
+    let data = png(image);
+    format!(
+        "\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=0:{}\x07",
+        data.len(),
+        area.width,
+        area.height,
+        base64::encode(&data)
+    )
+}
+
+/// Encodes with a 6x6x6 color cube. Mostly transparent pixels are left as the background.
+pub fn sixel(image: &Icon) -> String {
+    let (width, height) = image.dimensions();
+    let level = |c: u8| (c as u32 * 5 + 127) / 255;
+    let index = |x: u32, y: u32| {
+        let p = image.get_pixel(x, y);
+        if p[3] < 128 {
+            None
+        } else {
+            Some(level(p[0]) * 36 + level(p[1]) * 6 + level(p[2]))
+        }
+    };
+
+    let mut payload = format!("\x1bP0;1;0q\"1;1;{};{}", width, height);
+    for color in 0..216 {
+        let percent = |l: u32| l * 100 / 5;
+        payload.push_str(&format!(
+            "#{};2;{};{};{}",
+            color,
+            percent(color / 36),
+            percent(color / 6 % 6),
+            percent(color % 6)
+        ));
+    }
+    for band in (0..height).step_by(6) {
+        // Bits per column for every color present in this band.
+        let mut colors: HashMap<u32, Vec<u8>> = HashMap::new();
+        for y in band..(band + 6).min(height) {
+            for x in 0..width {
+                if let Some(color) = index(x, y) {
+                    let row = colors
+                        .entry(color)
+                        .or_insert_with(|| vec![0; width as usize]);
+                    row[x as usize] |= 1 << (y - band);
+                }
+            }
+        }
+        let mut colors = colors.into_iter().collect::<Vec<_>>();
+        colors.sort_by_key(|(color, _)| *color);
+        for (color, row) in colors {
+            payload.push_str(&format!("#{}", color));
+            let mut run = row.iter().peekable();
+            while let Some(&bits) = run.next() {
+                let mut count = 1;
+                while run.peek() == Some(&&bits) {
+                    run.next();
+                    count += 1;
+                }
+                let c = (63 + bits) as char;
+                if count > 3 {
+                    payload.push_str(&format!("!{}{}", count, c));
+                } else {
+                    payload.push_str(&c.to_string().repeat(count));
+                }
+            }
+            payload.push('$');
+        }
+        payload.push('-');
+    }
+    payload.push_str("\x1b\\");
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::graphics::sixel;
+    use crate::util::image::Icon;
+
+    #[test]
+    fn test_sixel_encoding() {
+        // A single opaque red column over a transparent one, 6 pixels tall.
+        let mut image = Icon::new(2, 6);
+        for y in 0..6 {
+            image.put_pixel(0, y, image::Rgba([255, 0, 0, 255]));
+        }
+        let payload = sixel(&image);
+        assert!(payload.starts_with("\x1bP0;1;0q\"1;1;2;6"));
+        assert!(payload.ends_with("\x1b\\"));
+        // Red is 5 * 36 in the cube, all six bits set then nothing.
+        assert!(payload.contains("#180~?$-"));
+    }
+}
@@ -1,34 +1,270 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
 use std::thread;
 
+use crate::util::error::STError;
+use crate::util::log::log;
 use crate::util::paths::{icon_exists, icon_save};
 
 use crate::interface::game::Game;
 
-pub fn update_img(
-    selected: &Option<&Game>,
-) -> Option<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>> {
-    if let Some(game) = selected {
-        if let Ok(path) = icon_exists(game.id) {
-            if let Some(path) = path.to_str() {
-                if let Ok(data) = image::open(path) {
-                    return Some(data.to_rgba8());
-                }
+use image::imageops::{resize, FilterType};
+use serde::{Deserialize, Serialize};
+
+pub type Icon = image::ImageBuffer<image::Rgba<u8>, Vec<u8>>;
+
+#[derive(PartialEq, Eq, Hash, Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub enum ArtworkKind {
+    #[default]
+    Icon,
+    Header,
+    Capsule,
+    Hero,
+    Logo,
+}
+
+impl ArtworkKind {
+    pub fn next(&self) -> ArtworkKind {
+        match self {
+            ArtworkKind::Icon => ArtworkKind::Header,
+            ArtworkKind::Header => ArtworkKind::Capsule,
+            ArtworkKind::Capsule => ArtworkKind::Hero,
+            ArtworkKind::Hero => ArtworkKind::Logo,
+            ArtworkKind::Logo => ArtworkKind::Icon,
+        }
+    }
+
+    /// Name of the cached file under the icon directory. Icons keep their original name so
+    /// existing caches still work.
+    pub fn file_name(&self, id: i32) -> String {
+        match self {
+            ArtworkKind::Icon => format!("{}.ico", id),
+            ArtworkKind::Header => format!("{}_header.jpg", id),
+            ArtworkKind::Capsule => format!("{}_capsule.jpg", id),
+            ArtworkKind::Hero => format!("{}_hero.jpg", id),
+            ArtworkKind::Logo => format!("{}_logo.png", id),
+        }
+    }
+}
+
+/// Largest (columns, rows) within the bounds that keeps the image's aspect ratio. Each cell
+/// holds two vertical pixels with the half block renderer.
+pub fn fit(image: &Icon, max_width: u16, max_height: u16) -> (u16, u16) {
+    let (width, height) = (image.width().max(1) as f32, image.height().max(1) as f32 / 2.);
+    let scale = (max_width as f32 / width).min(max_height as f32 / height);
+    (
+        ((width * scale) as u16).clamp(1, max_width.max(1)),
+        ((height * scale) as u16).clamp(1, max_height.max(1)),
+    )
+}
+
+/// Resizes to exactly fill a (columns, rows) area, smoother than the widget's own scaling.
+pub fn scale(image: &Icon, width: u16, height: u16) -> Icon {
+    resize(image, width as u32, 2 * height as u32, FilterType::Triangle)
+}
+
+pub enum Artwork {
+    Loading,
+    Missing,
+    Ready(Arc<Icon>),
+}
+
+/// Least recently used cache of decoded images. `None` records that a game has no usable image,
+/// so it isn't fetched again. Failed fetches are left out so they're tried again.
+struct Lru {
+    capacity: usize,
+    entries: VecDeque<(Key, Option<Arc<Icon>>)>,
+}
+
+type Key = (i32, ArtworkKind);
+
+impl Lru {
+    fn get(&mut self, id: Key) -> Option<Option<Arc<Icon>>> {
+        let position = self.entries.iter().position(|(key, _)| *key == id)?;
+        let entry = self.entries.remove(position)?;
+        let value = entry.1.clone();
+        self.entries.push_front(entry);
+        Some(value)
+    }
+
+    fn insert(&mut self, id: Key, icon: Option<Arc<Icon>>) {
+        self.entries.retain(|(key, _)| *key != id);
+        self.entries.push_front((id, icon));
+        self.entries.truncate(self.capacity);
+    }
+}
+
+struct Request {
+    generation: u64,
+    id: i32,
+    kind: ArtworkKind,
+    url: Option<String>,
+}
+
+/// Fetches and decodes images on a background thread so selection changes never block on the
+/// network. Only the most recent request is serviced, anything older is dropped.
+pub struct ImageLoader {
+    requests: Sender<Request>,
+    generation: Arc<AtomicU64>,
+    cache: Arc<Mutex<Lru>>,
+    // Fetches that failed for now, shown as missing but tried again when next requested.
+    failed: Arc<Mutex<HashSet<Key>>>,
+}
+
+impl ImageLoader {
+    pub fn new(capacity: usize) -> ImageLoader {
+        let (requests, receiver) = channel();
+        let loader = ImageLoader {
+            requests,
+            generation: Arc::new(AtomicU64::new(0)),
+            cache: Arc::new(Mutex::new(Lru {
+                capacity,
+                entries: VecDeque::new(),
+            })),
+            failed: Arc::new(Mutex::new(HashSet::new())),
+        };
+        let generation = loader.generation.clone();
+        let cache = loader.cache.clone();
+        let failed = loader.failed.clone();
+        thread::spawn(move || ImageLoader::work(receiver, generation, cache, failed));
+        loader
+    }
+
+    /// Marks the game as the one we want to see. Cached images are served immediately.
+    pub fn request(&self, selected: &Option<&Game>, kind: ArtworkKind, cdn: &str) {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Some(game) = selected {
+            if self.cache.lock().unwrap().get((game.id, kind)).is_some() {
+                return;
+            }
+            self.failed.lock().unwrap().remove(&(game.id, kind));
+            let _ = self.requests.send(Request {
+                generation,
+                id: game.id,
+                kind,
+                url: game.artwork_url(kind, cdn),
+            });
+        }
+    }
+
+    pub fn get(&self, selected: &Option<&Game>, kind: ArtworkKind) -> Artwork {
+        match selected {
+            Some(game) => match self.cache.lock().unwrap().get((game.id, kind)) {
+                Some(Some(icon)) => Artwork::Ready(icon),
+                Some(None) => Artwork::Missing,
+                None if self.failed.lock().unwrap().contains(&(game.id, kind)) => Artwork::Missing,
+                None => Artwork::Loading,
+            },
+            None => Artwork::Missing,
+        }
+    }
+
+    fn work(
+        receiver: Receiver<Request>,
+        generation: Arc<AtomicU64>,
+        cache: Arc<Mutex<Lru>>,
+        failed: Arc<Mutex<HashSet<Key>>>,
+    ) {
+        while let Ok(mut request) = receiver.recv() {
+            // Skip to the newest request, the rest are for games scrolled past.
+            while let Ok(newer) = receiver.try_recv() {
+                request = newer;
             }
-        } else if let Some(url) = &game.icon_url {
-            if let Ok(payload) = reqwest::blocking::get(url) {
-                if let Ok(bytes) = payload.bytes() {
-                    // cache here?
-                    let id = game.id;
-                    let to_save = bytes.clone();
-                    thread::spawn(move || {
-                        let _ = icon_save(id, &to_save);
-                    });
-                    if let Ok(data) = image::load_from_memory(&bytes) {
-                        return Some(data.to_rgba8());
-                    }
+            if request.generation != generation.load(Ordering::SeqCst) {
+                continue;
+            }
+            let file = request.kind.file_name(request.id);
+            let key = (request.id, request.kind);
+            match load(&file, &request.url) {
+                Ok(icon) => cache.lock().unwrap().insert(key, icon.map(Arc::new)),
+                Err(err) => {
+                    log!("Failed to fetch artwork", file, err);
+                    failed.lock().unwrap().insert(key);
                 }
             }
         }
     }
-    None
+}
+
+/// The image, or `None` when the game doesn't have one. Errors are worth trying again.
+fn load(file: &str, url: &Option<String>) -> Result<Option<Icon>, STError> {
+    if let Ok(path) = icon_exists(file) {
+        return Ok(image::open(path).ok().map(|data| data.to_rgba8()));
+    }
+    let bytes = match url {
+        Some(url) => fetch(url)?,
+        None => None,
+    };
+    let bytes = match bytes {
+        Some(bytes) => bytes,
+        None => return Ok(None),
+    };
+    if let Err(err) = icon_save(file, &bytes) {
+        log!("Failed to cache artwork", file, err);
+    }
+    Ok(image::load_from_memory(&bytes)
+        .ok()
+        .map(|data| data.to_rgba8()))
+}
+
+// Only a 404 means there's no such asset, anything else may work next time. Error pages from
+// the CDN are never cached as artwork.
+fn fetch(url: &str) -> Result<Option<Vec<u8>>, STError> {
+    let response = reqwest::blocking::get(url)?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    Ok(Some(response.error_for_status()?.bytes()?.to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::image::{fetch, fit, ArtworkKind, Icon, Lru};
+    use crate::util::test_server::serve;
+    use std::collections::VecDeque;
+
+    #[test]
+    fn test_lru_evicts_oldest() {
+        let mut lru = Lru {
+            capacity: 2,
+            entries: VecDeque::new(),
+        };
+        let key = |id| (id, ArtworkKind::Icon);
+        lru.insert(key(1), None);
+        lru.insert(key(2), None);
+        // Touching 1 makes 2 the oldest.
+        assert!(lru.get(key(1)).is_some());
+        lru.insert(key(3), None);
+        assert!(lru.get(key(2)).is_none());
+        assert!(lru.get(key(1)).is_some());
+        assert!(lru.get(key(3)).is_some());
+        assert!(lru.get((1, ArtworkKind::Header)).is_none());
+    }
+
+    #[test]
+    fn test_fit_keeps_aspect_ratio() {
+        // Headers are 460x215, so roughly 460 columns to 107 rows.
+        let header = Icon::new(460, 215);
+        assert_eq!(fit(&header, 92, 80), (92, 21));
+        // Square icons are twice as wide as they are tall in cells.
+        let icon = Icon::new(32, 32);
+        assert_eq!(fit(&icon, 160, 20), (40, 20));
+    }
+
+    #[test]
+    fn test_only_missing_artwork_is_final() {
+        let url = serve(
+            "header.jpg",
+            vec![
+                ("200 OK", "jpg"),
+                ("404 Not Found", "jpg"),
+                ("503 Service Unavailable", "jpg"),
+            ],
+        );
+        assert_eq!(fetch(&url).expect("Fetched"), Some(b"jpg".to_vec()));
+        assert_eq!(fetch(&url).expect("Fetched"), None);
+        assert!(fetch(&url).is_err());
+    }
 }
@@ -0,0 +1,430 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::util::error::STError;
+use crate::util::log::log;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+/// Everything that can be bound to a key while browsing the library.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize, Clone, Copy)]
+pub enum Action {
+    Search,
+    Install,
+    Login,
+    Launch,
+    Up,
+    Down,
+    Sort,
+    Collect,
+    Sidebar,
+    Quit,
+    Steam,
+    Refresh,
+    Favorite,
+    ShowFavorites,
+    Hide,
+    Artwork,
+    NextCollection,
+    PreviousCollection,
+    SaveSearch,
+    RemoveCollection,
+    Import,
+    Back,
+    GrowList,
+    ShrinkList,
+    GrowImage,
+    ShrinkImage,
+    MoveImage,
+    Details,
+    Mark,
+    Visual,
+    Uninstall,
+    PageUp,
+    PageDown,
+    HalfPageUp,
+    HalfPageDown,
+    Top,
+    Bottom,
+    Jump,
+    Find,
+    NextMatch,
+    PreviousMatch,
+    NextTab,
+    PreviousTab,
+    Fold,
+    HideDlc,
+    Extended,
+    ScrollDetailsDown,
+    ScrollDetailsUp,
+    Apps,
+}
+
+impl Action {
+    /// Label in the help bar, or `None` to leave the action out of it.
+    pub fn help(&self) -> Option<&'static str> {
+        match self {
+            Action::Search => Some("Search"),
+            Action::Install => Some("Download"),
+            Action::Login => Some("Accounts"),
+            Action::Launch => Some("Execute"),
+            Action::Up => Some("Up"),
+            Action::Down => Some("Down"),
+            Action::Sort => Some("Order"),
+            Action::Collect => Some("Collect"),
+            Action::Sidebar => Some("Collections"),
+            Action::Quit => Some("Quit"),
+            Action::Steam => Some("Steam"),
+            _ => None,
+        }
+    }
+}
+
+/// A key with modifiers, written like `ctrl+d`, `shift+tab`, `enter` or `G` in the config.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    /// Shift is already part of characters (and back tab), so it's dropped to make `F` and
+    /// `shift+f` the same key.
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Key {
+        let code = match code {
+            KeyCode::Char('\n') => KeyCode::Enter,
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            code => code,
+        };
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        Key { code, modifiers }
+    }
+
+    pub fn parse(spec: &str) -> Result<Key, STError> {
+        let (modifiers, key) = match spec.strip_suffix('+') {
+            Some(modifiers) => (modifiers, "+"),
+            None => match spec.rfind('+') {
+                Some(i) => (&spec[..i], &spec[i + 1..]),
+                None => ("", spec),
+            },
+        };
+        let mut mask = KeyModifiers::NONE;
+        for modifier in modifiers.split('+').filter(|m| !m.is_empty()) {
+            mask |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(STError::Problem(format!("Unknown modifier in {}", spec))),
+            };
+        }
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) if mask.contains(KeyModifiers::SHIFT) => {
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key.to_lowercase().as_str() {
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "space" => KeyCode::Char(' '),
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                name => match name.strip_prefix('f').map(str::parse::<u8>) {
+                    Some(Ok(n)) if (1..=12).contains(&n) => KeyCode::F(n),
+                    _ => return Err(STError::Problem(format!("Unknown key {}", spec))),
+                },
+            },
+        };
+        Ok(Key::new(code, mask))
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Key {
+        Key::new(event.code, event.modifiers)
+    }
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (modifier, name) in &[
+            (KeyModifiers::CONTROL, "Ctrl-"),
+            (KeyModifiers::ALT, "Alt-"),
+            (KeyModifiers::SHIFT, "Shift-"),
+        ] {
+            if self.modifiers.contains(*modifier) {
+                write!(f, "{}", name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+pub type Bindings = BTreeMap<Action, Vec<String>>;
+
+pub fn default_bindings() -> Bindings {
+    let bindings: &[(Action, &[&str])] = &[
+        (Action::Search, &["/"]),
+        (Action::Install, &["d"]),
+        (Action::Login, &["l"]),
+        (Action::Launch, &["enter"]),
+        (Action::Up, &["up", "k", "w"]),
+        (Action::Down, &["down", "j", "s"]),
+        (Action::Sort, &["o"]),
+        (Action::Collect, &["c"]),
+        (Action::Sidebar, &["C"]),
+        (Action::Quit, &["q"]),
+        (Action::Steam, &["space"]),
+        (Action::Refresh, &["r"]),
+        (Action::Favorite, &["f"]),
+        (Action::ShowFavorites, &["F"]),
+        (Action::Hide, &["H"]),
+        (Action::Artwork, &["a"]),
+        (Action::NextCollection, &["]"]),
+        (Action::PreviousCollection, &["["]),
+        (Action::SaveSearch, &["S"]),
+        (Action::RemoveCollection, &["X"]),
+        (Action::Import, &["I"]),
+        (Action::Back, &["esc"]),
+        (Action::GrowList, &[">"]),
+        (Action::ShrinkList, &["<"]),
+        (Action::GrowImage, &["+", "="]),
+        (Action::ShrinkImage, &["-"]),
+        (Action::MoveImage, &["p"]),
+        (Action::Details, &["i"]),
+        (Action::Mark, &["m"]),
+        (Action::Visual, &["V"]),
+        (Action::Uninstall, &["U"]),
+        (Action::PageUp, &["pageup"]),
+        (Action::PageDown, &["pagedown"]),
+        (Action::HalfPageUp, &["ctrl+u"]),
+        (Action::HalfPageDown, &["ctrl+d"]),
+        (Action::Top, &["g", "home"]),
+        (Action::Bottom, &["G", "end"]),
+        (Action::Jump, &["'"]),
+        (Action::Find, &["?"]),
+        (Action::NextMatch, &["n"]),
+        (Action::PreviousMatch, &["N"]),
+        (Action::NextTab, &["tab"]),
+        (Action::PreviousTab, &["shift+tab"]),
+        (Action::Fold, &["z"]),
+        (Action::HideDlc, &["D"]),
+        (Action::Extended, &["e"]),
+        (Action::ScrollDetailsDown, &["J"]),
+        (Action::ScrollDetailsUp, &["K"]),
+        (Action::Apps, &["A"]),
+    ];
+    bindings
+        .iter()
+        .map(|(action, keys)| (*action, keys.iter().map(|k| k.to_string()).collect()))
+        .collect()
+}
+
+/// Resolves key presses to actions. Actions missing from the config keep their default keys.
+/// A key claimed twice goes to the configured action over a default one, and clashes involving
+/// the config are reported in `conflicts`.
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    bindings: BTreeMap<Action, Vec<Key>>,
+    actions: HashMap<Key, Action>,
+    pub conflicts: Vec<String>,
+}
+
+impl Keymap {
+    pub fn new(config: &Bindings) -> Keymap {
+        let mut bindings = BTreeMap::new();
+        for (action, specs) in default_bindings().into_iter().chain(config.clone()) {
+            let keys = specs
+                .iter()
+                .filter_map(|spec| match Key::parse(spec) {
+                    Ok(key) => Some(key),
+                    Err(err) => {
+                        log!("Skipping binding", action, err);
+                        None
+                    }
+                })
+                .collect::<Vec<Key>>();
+            bindings.insert(action, keys);
+        }
+        let mut actions = HashMap::new();
+        let mut conflicts = vec![];
+        let (configured, defaults): (Vec<_>, Vec<_>) = bindings
+            .iter()
+            .partition(|(action, _)| config.contains_key(action));
+        for (action, keys) in configured.into_iter().chain(defaults) {
+            for key in keys {
+                match actions.get(key) {
+                    Some(taken) if taken != action && config.contains_key(action) => {
+                        let conflict = format!(
+                            "{} is bound to both {:?} and {:?}, using {:?}",
+                            key, taken, action, taken
+                        );
+                        log!("Conflicting binding", conflict);
+                        conflicts.push(conflict);
+                    }
+                    Some(_) => {}
+                    None => {
+                        actions.insert(*key, *action);
+                    }
+                }
+            }
+        }
+        // Keys lost to another action aren't shown for this one.
+        for (action, keys) in bindings.iter_mut() {
+            keys.retain(|key| actions.get(key) == Some(action));
+        }
+        Keymap {
+            bindings,
+            actions,
+            conflicts,
+        }
+    }
+
+    pub fn action(&self, event: KeyEvent) -> Option<Action> {
+        self.actions.get(&Key::from(event)).copied()
+    }
+
+    /// Keys bound to the action, for display.
+    pub fn keys(&self, action: Action) -> String {
+        self.bindings
+            .get(&action)
+            .map(|keys| {
+                keys.iter()
+                    .map(Key::to_string)
+                    .collect::<Vec<String>>()
+                    .join("/")
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn help(&self) -> String {
+        self.bindings
+            .keys()
+            .filter_map(|action| {
+                action
+                    .help()
+                    .map(|label| format!("[{}] {}", self.keys(*action), label))
+            })
+            .collect::<Vec<String>>()
+            .join(" | ")
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::new(&Bindings::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::keymap::{Action, Bindings, Key, Keymap};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    #[test]
+    fn test_parse_keys() {
+        let key = |code, modifiers| Key { code, modifiers };
+        assert_eq!(
+            Key::parse("ctrl+d").unwrap(),
+            key(KeyCode::Char('d'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(
+            Key::parse("shift+f").unwrap(),
+            key(KeyCode::Char('F'), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            Key::parse("ctrl++").unwrap(),
+            key(KeyCode::Char('+'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(
+            Key::parse("+").unwrap(),
+            key(KeyCode::Char('+'), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            Key::parse("shift+tab").unwrap(),
+            key(KeyCode::BackTab, KeyModifiers::NONE)
+        );
+        assert_eq!(
+            Key::parse("PageDown").unwrap(),
+            key(KeyCode::PageDown, KeyModifiers::NONE)
+        );
+        assert_eq!(
+            Key::parse("f5").unwrap(),
+            key(KeyCode::F(5), KeyModifiers::NONE)
+        );
+        assert!(Key::parse("hyper+x").is_err());
+        assert!(Key::parse("nope").is_err());
+        assert_eq!(
+            Key::parse("alt+space").unwrap().to_string(),
+            "Alt-Space".to_string()
+        );
+    }
+
+    #[test]
+    fn test_keymap_overrides() {
+        let mut config = Bindings::new();
+        config.insert(Action::Install, vec!["ctrl+b".to_string()]);
+        let keymap = Keymap::new(&config);
+        let event = |code, modifiers| KeyEvent::new(code, modifiers);
+
+        assert_eq!(
+            keymap.action(event(KeyCode::Char('b'), KeyModifiers::CONTROL)),
+            Some(Action::Install)
+        );
+        assert_eq!(
+            keymap.action(event(KeyCode::Char('d'), KeyModifiers::NONE)),
+            None
+        );
+        // Terminals report shift along with capitals.
+        assert_eq!(
+            keymap.action(event(KeyCode::Char('F'), KeyModifiers::SHIFT)),
+            Some(Action::ShowFavorites)
+        );
+        // Untouched actions keep their defaults.
+        assert_eq!(
+            keymap.action(event(KeyCode::Char('j'), KeyModifiers::NONE)),
+            Some(Action::Down)
+        );
+        assert!(keymap.help().starts_with("[/] Search | [Ctrl-b] Download"));
+        assert!(keymap.conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_keymap_conflicts() {
+        let mut config = Bindings::new();
+        config.insert(Action::Install, vec!["j".to_string(), "x".to_string()]);
+        config.insert(Action::Sort, vec!["x".to_string()]);
+        let keymap = Keymap::new(&config);
+        let event = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+
+        // Configured keys beat defaults, which quietly lose them.
+        assert_eq!(keymap.action(event('j')), Some(Action::Install));
+        assert_eq!(keymap.keys(Action::Down), "Down/s");
+        // Two configured actions on one key get reported.
+        assert_eq!(keymap.action(event('x')), Some(Action::Install));
+        assert_eq!(keymap.keys(Action::Sort), "");
+        assert_eq!(
+            keymap.conflicts,
+            vec!["x is bound to both Install and Sort, using Install".to_string()]
+        );
+    }
+}
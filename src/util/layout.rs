@@ -0,0 +1,229 @@
+use crate::util::image::{fit, Icon};
+
+use serde::{Deserialize, Serialize};
+use tui::layout::Rect;
+
+/// Corner of the detail pane the artwork is anchored to.
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub enum ImagePosition {
+    #[default]
+    BottomRight,
+    BottomLeft,
+    BottomCenter,
+}
+
+impl ImagePosition {
+    pub fn next(&self) -> ImagePosition {
+        match self {
+            ImagePosition::BottomRight => ImagePosition::BottomLeft,
+            ImagePosition::BottomLeft => ImagePosition::BottomCenter,
+            ImagePosition::BottomCenter => ImagePosition::BottomRight,
+        }
+    }
+}
+
+/// Optional rows of the detail pane, shown in the order listed in the config.
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Copy)]
+pub enum DetailRow {
+    Homepage,
+    Developer,
+    Publisher,
+    ProtonTier,
+    Deck,
+    // Owned DLC, each on its own line below a summary.
+    Dlc,
+    State,
+    Installation,
+    Size,
+}
+
+impl DetailRow {
+    /// Rows only known once steamcmd reports an install status.
+    pub fn needs_status(&self) -> bool {
+        matches!(
+            self,
+            DetailRow::State | DetailRow::Installation | DetailRow::Size
+        )
+    }
+}
+
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PaneLayout {
+    // Share of the width given to the game list, the detail pane gets the rest.
+    pub list_percent: u16,
+    // Share of the detail pane's width the artwork may take.
+    pub image_percent: u16,
+    // Really large images look super goofy, so cap them in cells.
+    pub image_max_width: u16,
+    pub image_max_height: u16,
+    pub image_position: ImagePosition,
+    pub detail_rows: Vec<DetailRow>,
+    // Below this many columns the detail pane becomes an overlay toggled with `i`.
+    pub narrow_width: u16,
+}
+
+impl Default for PaneLayout {
+    fn default() -> Self {
+        PaneLayout {
+            list_percent: 38,
+            image_percent: 100,
+            image_max_width: 160,
+            image_max_height: 80,
+            image_position: ImagePosition::default(),
+            detail_rows: vec![
+                DetailRow::Homepage,
+                DetailRow::Developer,
+                DetailRow::Publisher,
+                DetailRow::ProtonTier,
+                DetailRow::Deck,
+                DetailRow::Dlc,
+                DetailRow::State,
+                DetailRow::Installation,
+                DetailRow::Size,
+            ],
+            narrow_width: 80,
+        }
+    }
+}
+
+impl PaneLayout {
+    pub fn is_narrow(&self, width: u16) -> bool {
+        width < self.narrow_width
+    }
+
+    pub fn resize_list(&mut self, delta: i16) {
+        self.list_percent = (self.list_percent as i16 + delta).clamp(10, 90) as u16;
+    }
+
+    pub fn resize_image(&mut self, delta: i16) {
+        self.image_percent = (self.image_percent as i16 + delta).clamp(10, 100) as u16;
+    }
+
+    /// Lines the detail table takes, including the ID/Name header and spacers. The DLC row is
+    /// left out for games without any owned DLC.
+    pub fn detail_height(&self, has_status: bool, dlc: usize) -> u16 {
+        let (status, info): (Vec<&DetailRow>, Vec<&DetailRow>) =
+            self.detail_rows.iter().partition(|row| row.needs_status());
+        let mut height = 3 + info.len();
+        if info.contains(&&DetailRow::Dlc) {
+            height = height - 1 + if dlc > 0 { 1 + dlc } else { 0 };
+        }
+        if has_status && !status.is_empty() {
+            height += 1 + status.len();
+        }
+        height as u16
+    }
+
+    /// Where artwork goes within the detail pane, below `rows` lines of details. Incorrect image
+    /// placement leads to a hard crash, so bounds are explicitly calculated here and the area may
+    /// be empty.
+    pub fn image_area(&self, detail: Rect, rows: u16, image: Option<&Icon>) -> Rect {
+        let inner_width = detail.width.saturating_sub(2);
+        let max_width = (inner_width as u32 * self.image_percent.min(100) as u32 / 100) as u16;
+        let max_width = max_width.min(self.image_max_width);
+        // Borders, the detail rows and a gap above the image.
+        let max_height = detail
+            .height
+            .saturating_sub(rows + 3)
+            .min(self.image_max_height);
+        if max_width == 0 || max_height == 0 {
+            return Rect::new(detail.x, detail.y, 0, 0);
+        }
+        let (width, height) = match image {
+            // Respect the artwork's aspect ratio.
+            Some(image) => fit(image, max_width, max_height),
+            None => (max_width, max_height),
+        };
+        let (width, height) = (width.min(max_width), height.min(max_height));
+        let x = match self.image_position {
+            ImagePosition::BottomRight => (detail.x + detail.width).saturating_sub(width + 1),
+            ImagePosition::BottomLeft => detail.x + 1,
+            ImagePosition::BottomCenter => detail.x + (detail.width - width) / 2,
+        };
+        Rect {
+            x,
+            y: (detail.y + detail.height).saturating_sub(height + 1),
+            width,
+            height,
+        }
+    }
+}
+
+/// Area of an overlay covering most of `area`, used for details on narrow terminals.
+pub fn overlay(area: Rect) -> Rect {
+    let (margin_x, margin_y) = (area.width / 10, area.height / 10);
+    Rect {
+        x: area.x + margin_x,
+        y: area.y + margin_y,
+        width: area.width - 2 * margin_x,
+        height: area.height - 2 * margin_y,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::image::Icon;
+    use crate::util::layout::{overlay, DetailRow, ImagePosition, PaneLayout};
+    use tui::layout::Rect;
+
+    #[test]
+    fn test_image_area_stays_in_bounds() {
+        let mut layout = PaneLayout::default();
+        let detail = Rect::new(40, 0, 60, 30);
+        let rows = layout.detail_height(true, 0);
+        assert_eq!(rows, 12);
+
+        let area = layout.image_area(detail, rows, None);
+        assert_eq!(area, Rect::new(41, 14, 58, 15));
+
+        layout.image_percent = 50;
+        layout.image_position = ImagePosition::BottomLeft;
+        let header = Icon::new(460, 215);
+        let area = layout.image_area(detail, rows, Some(&header));
+        assert_eq!((area.x, area.width), (41, 28));
+        assert!(area.y + area.height < detail.y + detail.height);
+
+        // Too small for anything, but never out of bounds.
+        let tiny = Rect::new(5, 5, 3, 4);
+        let area = layout.image_area(tiny, rows, Some(&header));
+        assert_eq!((area.width, area.height), (0, 0));
+    }
+
+    #[test]
+    fn test_image_area_in_tiny_terminals() {
+        let layout = PaneLayout::default();
+        let icon = Icon::new(32, 32);
+        for &(width, height) in &[(0, 0), (1, 1), (2, 20), (40, 14), (40, 15), (u16::MAX, 1)] {
+            let detail = Rect::new(10, 3, width, height);
+            let area = layout.image_area(detail, 11, Some(&icon));
+            assert!(area.x >= detail.x && area.y >= detail.y);
+            assert!(area.right() <= detail.right() && area.bottom() <= detail.bottom());
+        }
+        // Just enough room for a single row below the details.
+        let area = layout.image_area(Rect::new(0, 0, 40, 15), 11, Some(&icon));
+        assert_eq!((area.width, area.height), (2, 1));
+    }
+
+    #[test]
+    fn test_detail_rows_and_resizing() {
+        let mut layout = PaneLayout {
+            detail_rows: vec![DetailRow::Developer, DetailRow::Size],
+            ..PaneLayout::default()
+        };
+        assert_eq!(layout.detail_height(false, 0), 4);
+        assert_eq!(layout.detail_height(true, 0), 6);
+        layout.detail_rows.push(DetailRow::Dlc);
+        assert_eq!(layout.detail_height(true, 0), 6);
+        assert_eq!(layout.detail_height(true, 2), 9);
+
+        layout.resize_list(100);
+        assert_eq!(layout.list_percent, 90);
+        layout.resize_image(-200);
+        assert_eq!(layout.image_percent, 10);
+
+        assert!(layout.is_narrow(79));
+        assert!(!layout.is_narrow(80));
+        assert_eq!(overlay(Rect::new(0, 0, 50, 20)), Rect::new(5, 2, 40, 16));
+    }
+}
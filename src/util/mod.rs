@@ -1,7 +1,15 @@
 pub mod error;
 pub mod event;
+pub mod graphics;
 pub mod image;
+pub mod keymap;
+pub mod layout;
 pub mod log;
 pub mod parser;
 pub mod paths;
+pub mod query;
+pub mod sort;
 pub mod stateful;
+#[cfg(test)]
+pub mod test_server;
+pub mod theme;
@@ -34,7 +34,7 @@ impl Lexer {
 lazy_static! {
     pub static ref INPUT_LEX: Lexer = Lexer::new(
         r#"(?x)
-           (login)\s+(\w) |
+           (login)\s+"?(\w) |
            (info) |
            (quit) |
            (licenses_print) |
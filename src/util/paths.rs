@@ -55,6 +55,14 @@ pub fn icon_directory() -> Result<PathBuf, STError> {
     mkdir(dir)
 }
 
+pub fn theme_directory() -> Result<PathBuf, STError> {
+    let dir = match env::var("STEAM_TUI_THEME_DIR") {
+        Ok(dir) => dir,
+        _ => format!("{}/themes", config_directory()?.as_path().display()),
+    };
+    mkdir(dir)
+}
+
 pub fn steam_directory() -> Result<PathBuf, STError> {
     let dir = match env::var("STEAM_APP_DIR") {
         Ok(dir) => dir,
@@ -63,6 +71,24 @@ pub fn steam_directory() -> Result<PathBuf, STError> {
     mkdir(dir)
 }
 
+// Unlike the other directories, this belongs to Steam so never create it.
+pub fn steam_userdata_directory() -> Result<PathBuf, STError> {
+    let dir = match env::var("STEAM_USERDATA_DIR") {
+        Ok(dir) => dir,
+        _ => "~/.steam/steam/userdata".to_string(),
+    };
+    let dir = shellexpand::full(&dir)?.to_string();
+    let dir = Path::new(&dir);
+    if dir.exists() {
+        Ok(dir.to_path_buf())
+    } else {
+        Err(STError::Problem(format!(
+            "Steam userdata doesn't exist: {:?}",
+            dir
+        )))
+    }
+}
+
 pub fn steam_run_wrapper(id: i32) -> Result<PathBuf, STError> {
     // Custom script always takes precedence, then env, then hardcoded path.
     let custom_script = script_directory()?.join(&format!("{}.sh", id));
@@ -93,10 +119,9 @@ pub fn executable_join(executable: &str, installdir: &str) -> Result<PathBuf, ST
     Ok(script_path)
 }
 
-pub fn icon_exists(id: i32) -> Result<PathBuf, STError> {
+pub fn icon_exists(file: &str) -> Result<PathBuf, STError> {
     let dir = icon_directory()?;
-    let icon = &format!("{}.ico", id);
-    let icon = Path::new(icon);
+    let icon = Path::new(file);
     let icon = dir.join(icon);
     if icon.exists() {
         Ok(icon)
@@ -105,10 +130,9 @@ pub fn icon_exists(id: i32) -> Result<PathBuf, STError> {
     }
 }
 
-pub fn icon_save(id: i32, icon: &[u8]) -> Result<(), STError> {
+pub fn icon_save(file: &str, icon: &[u8]) -> Result<(), STError> {
     let dir = icon_directory()?;
-    let icon_path = &format!("{}.ico", id);
-    let icon_path = Path::new(icon_path);
+    let icon_path = Path::new(file);
     let icon_path = dir.join(icon_path);
     let mut file = File::create(icon_path)?;
     file.write_all(icon)?;
@@ -172,15 +196,66 @@ pub fn config_location() -> Result<PathBuf, STError> {
     Ok(config_path)
 }
 
-pub fn cache_location() -> Result<PathBuf, STError> {
+// Account names are only letters, digits and underscores, anything else is kept out of the path.
+fn cache_name(user: &str) -> String {
+    let name = user
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!("{}.json", name)
+}
+
+fn cache_file(user: &str) -> Result<PathBuf, STError> {
+    let dir = mkdir(format!("{}/games", cache_directory()?.as_path().display()))?;
+    Ok(dir.join(cache_name(user)))
+}
+
+pub fn cache_location(user: &str) -> Result<PathBuf, STError> {
+    let cache_path = cache_file(user)?;
+    touch(&cache_path)?;
+    Ok(cache_path)
+}
+
+/// Caches from before accounts were kept apart belong to the account that was logged in.
+pub fn migrate_cache(user: &str) -> Result<(), STError> {
+    let legacy = cache_directory()?.join("games.json");
+    let cache_path = cache_file(user)?;
+    if !user.is_empty() && !cache_path.exists() && legacy.exists() {
+        fs::rename(legacy, &cache_path)?;
+    }
+    Ok(())
+}
+
+pub fn invalidate_cache(user: &str) -> Result<(), STError> {
+    match fs::remove_file(cache_file(user)?) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+pub fn proton_cache_location() -> Result<PathBuf, STError> {
     let dir = cache_directory()?;
-    let cache_path = Path::new("games.json");
+    let cache_path = Path::new("protondb.json");
     let cache_path = dir.join(cache_path);
     touch(&cache_path)?;
     Ok(cache_path)
 }
 
-pub fn invalidate_cache() -> Result<(), STError> {
-    fs::remove_file(cache_location()?)?;
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use crate::util::paths::cache_name;
+
+    #[test]
+    fn test_cache_name() {
+        assert_eq!(cache_name("gaben"), "gaben.json");
+        assert_eq!(cache_name("Gordon_17"), "Gordon_17.json");
+        assert_eq!(cache_name("../../.bashrc"), "_______bashrc.json");
+        assert_eq!(cache_name("a/b c"), "a_b_c.json");
+    }
 }
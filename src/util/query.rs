@@ -0,0 +1,318 @@
+use std::cmp::Ordering;
+
+use crate::interface::executable::Platform;
+use crate::interface::game::GameType;
+use crate::interface::metadata::DeckCategory;
+use crate::interface::proton_data::ProtonData;
+
+/// A single `key:value` restriction from the search bar.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Filter {
+    Name(String),
+    Developer(String),
+    Publisher(String),
+    Type(GameType),
+    Installed(bool),
+    // Tier, and whether anything better matches too. Native Linux games beat any tier.
+    Proton(String, bool),
+    Platform(Platform),
+    Size(Ordering, bool, f64),
+    Favorite(bool),
+    Collection(String),
+    Downloading(bool),
+    Update(bool),
+    Played(bool),
+    // Category, and whether anything better matches too.
+    Deck(DeckCategory, bool),
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Term {
+    pub negated: bool,
+    pub filter: Filter,
+}
+
+/// Parsed form of the search bar. Anything that isn't a filter, including words with a colon
+/// that isn't a filter name like `Half-Life:`, is joined back together and fuzzy matched against
+/// the name as before. Filters that fail to parse are dropped and reported in `errors`, so a half
+/// typed query still narrows the list.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Query {
+    pub text: String,
+    pub terms: Vec<Term>,
+    pub errors: Vec<String>,
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Query {
+        let mut parsed = Query::default();
+        let mut text = vec![];
+        for token in tokenize(query) {
+            let (negated, body) = match token.strip_prefix('-') {
+                Some(body) if !body.is_empty() => (true, body),
+                _ => (false, token.as_str()),
+            };
+            match body
+                .split_once(':')
+                .map(|(key, value)| Query::filter(key, value))
+            {
+                Some(Ok(Some(filter))) => parsed.terms.push(Term { negated, filter }),
+                Some(Err(err)) => parsed.errors.push(err),
+                _ if negated => parsed.terms.push(Term {
+                    negated,
+                    filter: Filter::Name(body.to_lowercase()),
+                }),
+                _ => text.push(token.clone()),
+            }
+        }
+        parsed.text = text.join(" ");
+        parsed
+    }
+
+    // `None` when the key isn't a filter at all.
+    fn filter(key: &str, value: &str) -> Result<Option<Filter>, String> {
+        let value = value.to_lowercase();
+        let filter = match key.to_lowercase().as_str() {
+            "dev" | "developer" => Ok(Filter::Developer(required(key, value)?)),
+            "pub" | "publisher" => Ok(Filter::Publisher(required(key, value)?)),
+            "type" => match value.as_str() {
+                "game" => Ok(Filter::Type(GameType::Game)),
+                "dlc" => Ok(Filter::Type(GameType::DLC)),
+                "driver" => Ok(Filter::Type(GameType::Driver)),
+                "application" | "app" => Ok(Filter::Type(GameType::Application)),
+                "config" => Ok(Filter::Type(GameType::Config)),
+                "demo" => Ok(Filter::Type(GameType::Demo)),
+                "tool" => Ok(Filter::Type(GameType::Tool)),
+                "unknown" => Ok(Filter::Type(GameType::Unknown)),
+                _ => Err(format!("unknown type '{}'", value)),
+            },
+            "installed" => Ok(Filter::Installed(boolean(key, &value)?)),
+            "fav" | "favorite" => Ok(Filter::Favorite(boolean(key, &value)?)),
+            "downloading" => Ok(Filter::Downloading(boolean(key, &value)?)),
+            "update" | "updates" => Ok(Filter::Update(boolean(key, &value)?)),
+            "played" => Ok(Filter::Played(boolean(key, &value)?)),
+            "deck" => {
+                let (at_least, category) = match value.strip_prefix(">=") {
+                    Some(category) => (true, category),
+                    None => (false, value.as_str()),
+                };
+                match DeckCategory::parse(category) {
+                    Some(category) => Ok(Filter::Deck(category, at_least)),
+                    None => Err(format!("unknown deck rating '{}'", category)),
+                }
+            }
+            "proton" => match value.strip_prefix(">=") {
+                Some(tier) if ProtonData::rank(tier) > 0 => {
+                    Ok(Filter::Proton(tier.to_string(), true))
+                }
+                Some(tier) => Err(format!("unknown proton tier '{}'", tier)),
+                None => Ok(Filter::Proton(required(key, value)?, false)),
+            },
+            "col" | "collection" => Ok(Filter::Collection(required(key, value)?)),
+            "platform" | "os" => match value.as_str() {
+                "linux" => Ok(Filter::Platform(Platform::Linux)),
+                "windows" | "win" => Ok(Filter::Platform(Platform::Windows)),
+                "mac" | "macos" => Ok(Filter::Platform(Platform::Mac)),
+                _ => Err(format!("unknown platform '{}'", value)),
+            },
+            "size" => {
+                let (ordering, inclusive, amount) = match value.as_bytes().first() {
+                    Some(b'>') if value.starts_with(">=") => (Ordering::Greater, true, &value[2..]),
+                    Some(b'<') if value.starts_with("<=") => (Ordering::Less, true, &value[2..]),
+                    Some(b'>') => (Ordering::Greater, false, &value[1..]),
+                    Some(b'<') => (Ordering::Less, false, &value[1..]),
+                    Some(b'=') => (Ordering::Equal, true, &value[1..]),
+                    _ => (Ordering::Greater, true, value.as_str()),
+                };
+                Ok(Filter::Size(ordering, inclusive, bytes(amount)?))
+            }
+            _ => return Ok(None),
+        };
+        filter.map(Some)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty() && self.terms.is_empty()
+    }
+}
+
+impl Filter {
+    /// Compares a size against a `Filter::Size`, everything else is a miss.
+    pub fn size_matches(&self, size: f64) -> bool {
+        match self {
+            Filter::Size(ordering, inclusive, amount) => {
+                let compared = size.partial_cmp(amount).unwrap_or(Ordering::Equal);
+                compared == *ordering || (*inclusive && compared == Ordering::Equal)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn required(key: &str, value: String) -> Result<String, String> {
+    if value.is_empty() {
+        Err(format!("'{}:' needs a value", key))
+    } else {
+        Ok(value)
+    }
+}
+
+fn boolean(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "" | "yes" | "y" | "true" => Ok(true),
+        "no" | "n" | "false" => Ok(false),
+        _ => Err(format!("'{}:' expects yes or no", key)),
+    }
+}
+
+// Sizes are displayed in powers of 1000 by pretty_bytes, so match that here.
+fn bytes(amount: &str) -> Result<f64, String> {
+    let amount = amount.trim_end_matches('b');
+    let (number, scale) = match amount.chars().last() {
+        Some('k') => (&amount[..amount.len() - 1], 1e3),
+        Some('m') => (&amount[..amount.len() - 1], 1e6),
+        Some('g') => (&amount[..amount.len() - 1], 1e9),
+        Some('t') => (&amount[..amount.len() - 1], 1e12),
+        _ => (amount, 1.),
+    };
+    number
+        .parse::<f64>()
+        .map(|number| number * scale)
+        .map_err(|_| format!("bad size '{}'", amount))
+}
+
+/// Quotes a filter value so it comes back from `Query::parse` as is.
+pub fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Whitespace split, but `dev:"double fine"` stays as one token. A backslash keeps the next
+// character as is, quotes included.
+fn tokenize(query: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    let mut quoted = false;
+    let mut chars = query.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => token.extend(chars.next()),
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(token.clone());
+                    token.clear();
+                }
+            }
+            c => token.push(c),
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interface::executable::Platform;
+    use crate::interface::metadata::DeckCategory;
+    use crate::util::query::{quote, Filter, Query};
+
+    #[test]
+    fn test_parse_query() {
+        let query =
+            Query::parse(r#"half dev:valve -platform:linux pub:"double fine" life col:"Co-op""#);
+        assert_eq!(query.text, "half life");
+        assert!(query.errors.is_empty());
+        assert_eq!(query.terms.len(), 4);
+        assert_eq!(
+            query.terms[0].filter,
+            Filter::Developer("valve".to_string())
+        );
+        assert!(query.terms[1].negated);
+        assert_eq!(query.terms[1].filter, Filter::Platform(Platform::Linux));
+        assert_eq!(
+            query.terms[2].filter,
+            Filter::Publisher("double fine".to_string())
+        );
+        assert_eq!(
+            query.terms[3].filter,
+            Filter::Collection("co-op".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_status_filters() {
+        let query = Query::parse("downloading: -update: played:no deck:>=playable deck:Verified");
+        assert!(query.errors.is_empty());
+        assert_eq!(
+            query.terms[3].filter,
+            Filter::Deck(DeckCategory::Playable, true)
+        );
+        assert_eq!(
+            query.terms[4].filter,
+            Filter::Deck(DeckCategory::Verified, false)
+        );
+        assert!(!Query::parse("deck:great").errors.is_empty());
+        assert_eq!(query.terms[0].filter, Filter::Downloading(true));
+        assert!(query.terms[1].negated);
+        assert_eq!(query.terms[1].filter, Filter::Update(true));
+        assert_eq!(query.terms[2].filter, Filter::Played(false));
+    }
+
+    #[test]
+    fn test_parse_proton_filters() {
+        let query = Query::parse("proton:>=Gold proton:native proton:silv");
+        assert!(query.errors.is_empty());
+        assert_eq!(
+            query.terms[0].filter,
+            Filter::Proton("gold".to_string(), true)
+        );
+        assert_eq!(
+            query.terms[1].filter,
+            Filter::Proton("native".to_string(), false)
+        );
+        assert_eq!(
+            query.terms[2].filter,
+            Filter::Proton("silv".to_string(), false)
+        );
+        assert!(!Query::parse("proton:>=shiny").errors.is_empty());
+    }
+
+    #[test]
+    fn test_quoted_values() {
+        let value = r#"say "hi" \o/"#;
+        let query = Query::parse(&format!("dev:{} portal", quote(value)));
+        assert_eq!(query.text, "portal");
+        assert_eq!(query.terms[0].filter, Filter::Developer(value.to_string()));
+    }
+
+    #[test]
+    fn test_parse_query_errors() {
+        let query = Query::parse("portal size:>ten installed:maybe");
+        assert_eq!(query.text, "portal");
+        assert!(query.terms.is_empty());
+        assert_eq!(query.errors.len(), 2);
+    }
+
+    #[test]
+    fn test_names_with_colons() {
+        let query = Query::parse("Half-Life: Alyx dev:valve");
+        assert_eq!(query.text, "Half-Life: Alyx");
+        assert!(query.errors.is_empty());
+        assert_eq!(query.terms.len(), 1);
+        let query = Query::parse("-Warhammer 40,000: Dawn of War");
+        assert_eq!(query.text, "40,000: Dawn of War");
+        assert_eq!(query.terms[0].filter, Filter::Name("warhammer".to_string()));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        let query = Query::parse("size:>10G size:<=500mb");
+        let (larger, smaller) = (&query.terms[0].filter, &query.terms[1].filter);
+        assert!(larger.size_matches(12e9));
+        assert!(!larger.size_matches(10e9));
+        assert!(smaller.size_matches(500e6));
+        assert!(!smaller.size_matches(501e6));
+    }
+}
@@ -0,0 +1,179 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use crate::config::Config;
+use crate::interface::game::Game;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub enum SortOrder {
+    #[default]
+    Name,
+    Installed,
+    Size,
+    LastPlayed,
+    ReleaseDate,
+    Developer,
+    ProtonTier,
+    Deck,
+}
+
+impl SortOrder {
+    /// Cycles through orders in the order they are declared.
+    pub fn next(&self) -> SortOrder {
+        match self {
+            SortOrder::Name => SortOrder::Installed,
+            SortOrder::Installed => SortOrder::Size,
+            SortOrder::Size => SortOrder::LastPlayed,
+            SortOrder::LastPlayed => SortOrder::ReleaseDate,
+            SortOrder::ReleaseDate => SortOrder::Developer,
+            SortOrder::Developer => SortOrder::ProtonTier,
+            SortOrder::ProtonTier => SortOrder::Deck,
+            SortOrder::Deck => SortOrder::Name,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SortOrder::Name => "name",
+            SortOrder::Installed => "installed",
+            SortOrder::Size => "size",
+            SortOrder::LastPlayed => "last played",
+            SortOrder::ReleaseDate => "release date",
+            SortOrder::Developer => "developer",
+            SortOrder::ProtonTier => "proton tier",
+            SortOrder::Deck => "deck rating",
+        }
+    }
+
+    /// Compares two games under this order. Everything but name and developer puts the "most"
+    /// first, and ties always fall back to name so the list is stable.
+    pub fn compare(&self, a: &Game, b: &Game, config: &Config) -> Ordering {
+        let by_name = || a.name.to_lowercase().cmp(&b.name.to_lowercase());
+        let ordering = match self {
+            SortOrder::Name => Ordering::Equal,
+            SortOrder::Installed => b.is_installed().cmp(&a.is_installed()),
+            SortOrder::Size => b.size().partial_cmp(&a.size()).unwrap_or(Ordering::Equal),
+            SortOrder::LastPlayed => config
+                .last_played
+                .get(&b.id)
+                .cmp(&config.last_played.get(&a.id)),
+            SortOrder::ReleaseDate => b.release_date.cmp(&a.release_date),
+            SortOrder::Developer => a.developer.to_lowercase().cmp(&b.developer.to_lowercase()),
+            SortOrder::ProtonTier => b.proton_rank().cmp(&a.proton_rank()),
+            SortOrder::Deck => b.metadata.deck.category.cmp(&a.metadata.deck.category),
+        };
+        ordering.then_with(by_name)
+    }
+}
+
+/// Positions that put every DLC right after its base game, given `(id, parent)` pairs in their
+/// sorted order. DLC keep their order among themselves, and ones without their game in the list
+/// stay where they are.
+pub fn nest_dlc(entries: &[(i32, Option<i32>)]) -> HashMap<i32, usize> {
+    let ids = entries.iter().map(|(id, _)| *id).collect::<HashSet<i32>>();
+    let mut children: HashMap<i32, Vec<i32>> = HashMap::new();
+    for (id, parent) in entries {
+        match parent {
+            Some(parent) if ids.contains(parent) && parent != id => {
+                children.entry(*parent).or_default().push(*id)
+            }
+            _ => {}
+        }
+    }
+    let mut positions = HashMap::new();
+    for (id, parent) in entries {
+        if parent.is_some_and(|parent| children.contains_key(&parent) && parent != *id) {
+            continue;
+        }
+        let next = positions.len();
+        positions.entry(*id).or_insert(next);
+        for child in children.get(id).into_iter().flatten() {
+            let next = positions.len();
+            positions.entry(*child).or_insert(next);
+        }
+    }
+    // Anything nested deeper than one level goes at the end rather than missing.
+    for (id, _) in entries {
+        let next = positions.len();
+        positions.entry(*id).or_insert(next);
+    }
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::interface::game::Game;
+    use crate::util::sort::{nest_dlc, SortOrder};
+    use std::cmp::Ordering;
+
+    fn sorted(order: SortOrder, games: &[Game], config: &Config) -> Vec<i32> {
+        let mut games = games.to_vec();
+        games.sort_by(|a, b| order.compare(a, b, config));
+        games.iter().map(|game| game.id).collect()
+    }
+
+    #[test]
+    fn test_sort_order_cycles() {
+        let mut order = SortOrder::default();
+        for _ in 0..8 {
+            order = order.next();
+        }
+        assert_eq!(order, SortOrder::Name);
+    }
+
+    #[test]
+    fn test_nest_dlc() {
+        // Sorted by name: the DLC come first, one belongs to a game that isn't owned.
+        let entries = [
+            (11, Some(2)),
+            (12, Some(1)),
+            (13, Some(99)),
+            (1, None),
+            (14, Some(2)),
+            (2, None),
+        ];
+        let positions = nest_dlc(&entries);
+        let mut order = entries.iter().map(|(id, _)| *id).collect::<Vec<i32>>();
+        order.sort_by_key(|id| positions[id]);
+        assert_eq!(order, vec![13, 1, 12, 2, 11, 14]);
+    }
+
+    #[test]
+    fn test_compare() {
+        let games = [
+            Game::fixture(1, "portal", None),
+            Game::fixture(2, "Half-Life", Some(("Fully Installed", 10.))),
+            Game::fixture(3, "alyx", Some(("Fully Installed", 60.))),
+            Game::fixture(4, "Dota", None),
+            Game::fixture(5, "braid", Some(("Fully Installed", 10.))),
+        ];
+        let mut config = Config::default();
+        config.last_played.insert(1, 100);
+        config.last_played.insert(4, 200);
+
+        // Ties fall back to name, ignoring case.
+        assert_eq!(
+            sorted(SortOrder::Name, &games, &config),
+            vec![3, 5, 4, 2, 1]
+        );
+        assert_eq!(
+            sorted(SortOrder::Installed, &games, &config),
+            vec![3, 5, 2, 4, 1]
+        );
+        assert_eq!(
+            sorted(SortOrder::Size, &games, &config),
+            vec![3, 5, 2, 4, 1]
+        );
+        assert_eq!(
+            sorted(SortOrder::LastPlayed, &games, &config),
+            vec![4, 1, 3, 5, 2]
+        );
+        assert_eq!(
+            SortOrder::Size.compare(&games[1], &games[1], &config),
+            Ordering::Equal
+        );
+    }
+}
@@ -1,64 +1,185 @@
+use std::cmp::Ordering;
+
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
 use tui::widgets::ListState;
 
+use crate::config::Config;
+use crate::util::query::{Filter, Query};
+
 pub trait Named {
-    fn get_name(&self) -> String;
-    fn is_valid(&self) -> bool;
+    fn get_name(&self, config: &Config) -> String;
+    fn is_valid(&self, config: &Config) -> bool;
+    fn matches(&self, filter: &Filter, config: &Config) -> bool;
+}
+
+fn matches_query<T: Named>(
+    matcher: &SkimMatcherV2,
+    nameable: &T,
+    query: &Query,
+    config: &Config,
+) -> bool {
+    matcher
+        .fuzzy_match(&nameable.get_name(config), &query.text)
+        .is_some()
+        && query
+            .terms
+            .iter()
+            .all(|term| nameable.matches(&term.filter, config) != term.negated)
 }
 
+/// A filterable list. Filtering is expensive with large libraries, so the indices of the
+/// activated items are cached and only recomputed when the query, scope, config or items change.
 #[derive(Clone)]
 pub struct StatefulList<T> {
     pub state: ListState,
-    pub items: Vec<T>,
-    pub query: String,
+    items: Vec<T>,
+    query: String,
+    // Query for the active collection, applied on top of the search query.
+    scope: String,
+    config: Config,
+    activated: Vec<usize>,
+    // Mirror of the scroll offset tui keeps private in `ListState`.
+    offset: usize,
+    // Visible rows as of the last `scroll`, for paging.
+    rows: usize,
 }
 
 impl<T: Named> StatefulList<T> {
     pub fn new() -> StatefulList<T> {
-        StatefulList {
-            state: ListState::default(),
-            items: Vec::new(),
-            query: "".to_string(),
-        }
+        StatefulList::with_items(Vec::new(), &Config::default())
     }
 
-    pub fn with_items(items: Vec<T>) -> StatefulList<T> {
-        StatefulList {
+    pub fn with_items(items: Vec<T>, config: &Config) -> StatefulList<T> {
+        let mut list = StatefulList {
             state: ListState::default(),
             items,
             query: "".to_string(),
-        }
+            scope: "".to_string(),
+            config: config.clone(),
+            activated: Vec::new(),
+            offset: 0,
+            rows: 0,
+        };
+        list.refresh();
+        list
     }
 
     pub fn is_empty(&self) -> bool {
         self.items.is_empty()
     }
 
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn set_query(&mut self, query: String) {
+        self.query = query;
+        self.refresh();
+    }
+
+    pub fn push_query(&mut self, c: char) {
+        self.query.push(c);
+        self.refresh();
+    }
+
+    pub fn pop_query(&mut self) {
+        self.query.pop();
+        self.refresh();
+    }
+
+    pub fn set_scope(&mut self, scope: String) {
+        self.scope = scope;
+        self.refresh();
+    }
+
+    pub fn set_config(&mut self, config: &Config) {
+        self.config = config.clone();
+        self.refresh();
+    }
+
+    /// The config snapshot names and filters are evaluated against.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn name(&self, item: &T) -> String {
+        item.get_name(&self.config)
+    }
+
     pub fn selected(&self) -> Option<&T> {
         self.state.selected().map(|i| {
-            *self
-                .activated()
+            &self.items[*self
+                .activated
                 .get(i)
-                .expect("Index is guarded by next, previous. This is safe.")
+                .expect("Index is guarded by next, previous and refresh. This is safe.")]
         })
     }
 
+    /// Every item, filtered or not.
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
     pub fn activated(&self) -> Vec<&T> {
+        self.activated.iter().map(|&i| &self.items[i]).collect()
+    }
+
+    /// Recomputes the activated items. Useful when items change state underneath the list (e.g.
+    /// an install finishing while filtering on `installed:`). The selected item is kept if it is
+    /// still visible, otherwise the selection is clamped to the list.
+    pub fn refresh(&mut self) {
+        let previous = self
+            .state
+            .selected()
+            .and_then(|i| self.activated.get(i).copied());
+
         let matcher = SkimMatcherV2::default();
-        self.items
+        let queries = [Query::parse(&self.scope), Query::parse(&self.query)];
+        let config = &self.config;
+        self.activated = self
+            .items
             .iter()
-            .filter(|nameable| {
-                matcher
-                    .fuzzy_match(&nameable.get_name(), &self.query)
-                    .is_some()
+            .enumerate()
+            .filter(|(_, nameable)| nameable.is_valid(config))
+            .filter(|(_, nameable)| {
+                queries
+                    .iter()
+                    .all(|query| matches_query(&matcher, *nameable, query, config))
             })
-            .filter(|nameable| nameable.is_valid())
-            .collect::<Vec<_>>()
+            .map(|(i, _)| i)
+            .collect();
+
+        if let Some(i) = self.state.selected() {
+            let kept = previous.and_then(|p| self.activated.iter().position(|&a| a == p));
+            self.state.select(match kept {
+                Some(kept) => Some(kept),
+                None if self.activated.is_empty() => None,
+                None => Some(i.min(self.activated.len() - 1)),
+            });
+        }
+    }
+
+    /// Filters may depend on state that changes underneath the list, plain names don't.
+    pub fn has_filters(&self) -> bool {
+        [&self.scope, &self.query]
+            .iter()
+            .any(|query| !Query::parse(query).terms.is_empty())
+    }
+
+    /// Reorders the underlying items. Indices shift, so any selection goes back to the top.
+    pub fn sort_by<F>(&mut self, compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        self.items.sort_by(compare);
+        self.state.select(None);
+        self.refresh();
+        self.restart();
     }
 
     pub fn restart(&mut self) {
-        if self.activated().is_empty() {
+        if self.activated.is_empty() {
             self.state.select(None);
         } else {
             self.state.select(Some(0));
@@ -68,14 +189,14 @@ impl<T: Named> StatefulList<T> {
     pub fn next(&mut self) {
         let i = match self.state.selected() {
             Some(i) => {
-                if i >= self.activated().len() - 1 {
+                if i >= self.activated.len() - 1 {
                     Some(0)
                 } else {
                     Some(i + 1)
                 }
             }
             None => {
-                if !self.activated().is_empty() {
+                if !self.activated.is_empty() {
                     Some(0)
                 } else {
                     None
@@ -89,13 +210,13 @@ impl<T: Named> StatefulList<T> {
         let i = match self.state.selected() {
             Some(i) => {
                 if i == 0 {
-                    Some(self.activated().len() - 1)
+                    Some(self.activated.len() - 1)
                 } else {
                     Some(i - 1)
                 }
             }
             None => {
-                if !self.activated().is_empty() {
+                if !self.activated.is_empty() {
                     Some(0)
                 } else {
                     None
@@ -104,6 +225,112 @@ impl<T: Named> StatefulList<T> {
         };
         self.state.select(i);
     }
+    /// Selects the nth activated item, clamped to the list.
+    pub fn select(&mut self, i: usize) {
+        if !self.activated.is_empty() {
+            self.state.select(Some(i.min(self.activated.len() - 1)));
+        }
+    }
+
+    /// Selects the first visible item the predicate holds for.
+    pub fn select_by<F>(&mut self, found: F) -> bool
+    where
+        F: Fn(&T) -> bool,
+    {
+        let items = &self.items;
+        let position = self.activated.iter().position(|&i| found(&items[i]));
+        if position.is_some() {
+            self.state.select(position);
+        }
+        position.is_some()
+    }
+
+    /// Moves the selection by `delta` items, stopping at either end instead of wrapping.
+    pub fn move_by(&mut self, delta: isize) {
+        let i = self.state.selected().unwrap_or(0) as isize + delta;
+        self.select(i.max(0) as usize);
+    }
+
+    /// Rows visible at once, at least one.
+    pub fn rows(&self) -> usize {
+        self.rows.max(1)
+    }
+
+    /// Selects the next item whose name starts with the letter, wrapping around.
+    pub fn jump_to(&mut self, letter: char) -> bool {
+        let letter = letter.to_lowercase().collect::<String>();
+        let config = self.config.clone();
+        self.seek(true, false, |item| {
+            // Skip decorations like the favorite heart.
+            item.get_name(&config)
+                .trim_start_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase()
+                .starts_with(&letter)
+        })
+    }
+
+    /// Selects the next (or previous) item matching a search query, without filtering the list.
+    /// With `inclusive` the selection itself counts as a match.
+    pub fn find(&mut self, query: &str, forward: bool, inclusive: bool) -> bool {
+        let matcher = SkimMatcherV2::default();
+        let query = Query::parse(query);
+        let config = self.config.clone();
+        self.seek(forward, inclusive, |item| {
+            matches_query(&matcher, item, &query, &config)
+        })
+    }
+
+    fn seek<F>(&mut self, forward: bool, inclusive: bool, found: F) -> bool
+    where
+        F: Fn(&T) -> bool,
+    {
+        let count = self.activated.len();
+        let start = self.state.selected().unwrap_or(0);
+        let skip = if inclusive { 0 } else { 1 };
+        let next = (skip..count + skip)
+            .map(|step| {
+                if forward {
+                    (start + step) % count
+                } else {
+                    (start + count - step % count) % count
+                }
+            })
+            .find(|&i| found(&self.items[self.activated[i]]));
+        if let Some(i) = next {
+            self.state.select(Some(i));
+        }
+        next.is_some()
+    }
+
+    /// Scrolls the way tui's `List` does, so clicks can be mapped to items. Call after rendering
+    /// with the number of visible rows.
+    pub fn scroll(&mut self, rows: usize) {
+        self.rows = rows;
+        if self.activated.is_empty() || rows == 0 {
+            return;
+        }
+        let selected = match self.state.selected() {
+            Some(i) => i.min(self.activated.len() - 1),
+            None => {
+                self.offset = 0;
+                0
+            }
+        };
+        let mut start = self.offset.min(self.activated.len() - 1);
+        if selected >= (start + rows).min(self.activated.len()) {
+            start = selected + 1 - rows;
+        }
+        if selected < start {
+            start = selected;
+        }
+        self.offset = start;
+    }
+
+    /// Index of the activated item drawn on a visible row, as of the last `scroll`.
+    pub fn at_row(&self, row: usize) -> Option<usize> {
+        Some(self.offset + row).filter(|&i| i < self.activated.len())
+    }
+
     pub fn unselect(&mut self) {
         self.state.select(None);
     }
@@ -114,3 +341,118 @@ impl<T: Named> Default for StatefulList<T> {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::util::query::Filter;
+    use crate::util::stateful::{Named, StatefulList};
+
+    struct Item(i32, &'static str);
+
+    impl Named for Item {
+        fn get_name(&self, _config: &Config) -> String {
+            self.1.to_string()
+        }
+        fn is_valid(&self, config: &Config) -> bool {
+            !config.hidden_games.contains(&self.0)
+        }
+        fn matches(&self, _filter: &Filter, _config: &Config) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_refresh_keeps_selection() {
+        let items = vec![Item(1, "alpha"), Item(2, "beta"), Item(3, "gamma")];
+        let mut config = Config::default();
+        let mut list = StatefulList::with_items(items, &config);
+        list.next();
+        list.next();
+        assert_eq!(list.selected().map(|item| item.0), Some(2));
+
+        // Hiding something above the selection shouldn't move it.
+        config.hidden_games.push(1);
+        list.set_config(&config);
+        assert_eq!(list.activated().len(), 2);
+        assert_eq!(list.selected().map(|item| item.0), Some(2));
+
+        // Filtering the selection away clamps to what's left.
+        list.set_query("gam".to_string());
+        assert_eq!(list.selected().map(|item| item.0), Some(3));
+        list.set_query("nothing matches".to_string());
+        assert!(list.selected().is_none());
+    }
+
+    #[test]
+    fn test_scroll_follows_selection() {
+        let items = (0..10).map(|i| Item(i, "item")).collect();
+        let mut list = StatefulList::with_items(items, &Config::default());
+        list.select(7);
+        list.scroll(3);
+        // Rows 5, 6 and 7 are visible.
+        assert_eq!(list.at_row(0), Some(5));
+        assert_eq!(list.at_row(2), Some(7));
+
+        // Moving within the window doesn't scroll, moving above it does.
+        list.select(6);
+        list.scroll(3);
+        assert_eq!(list.at_row(0), Some(5));
+        list.select(2);
+        list.scroll(3);
+        assert_eq!(list.at_row(0), Some(2));
+
+        list.select(42);
+        list.scroll(3);
+        assert_eq!(list.selected().map(|item| item.0), Some(9));
+        assert_eq!(list.at_row(2), Some(9));
+        assert_eq!(list.at_row(3), None);
+    }
+
+    #[test]
+    fn test_motions_and_find() {
+        let names = ["alpha", "Beta", "bravo", "charlie", "delta", "echo"];
+        let items = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Item(i as i32, name));
+        let mut list = StatefulList::with_items(items.collect(), &Config::default());
+        let selected = |list: &StatefulList<Item>| list.selected().map(|item| item.0);
+        list.restart();
+
+        list.move_by(4);
+        assert_eq!(selected(&list), Some(4));
+        list.move_by(10);
+        assert_eq!(selected(&list), Some(5));
+        list.move_by(-10);
+        assert_eq!(selected(&list), Some(0));
+
+        // Selecting by predicate leaves the selection alone when nothing matches.
+        assert!(list.select_by(|item| item.1 == "delta"));
+        assert_eq!(selected(&list), Some(4));
+        assert!(!list.select_by(|item| item.1 == "foxtrot"));
+        assert_eq!(selected(&list), Some(4));
+        list.move_by(-10);
+
+        // Letters are case insensitive and wrap around.
+        assert!(list.jump_to('b'));
+        assert_eq!(selected(&list), Some(1));
+        assert!(list.jump_to('B'));
+        assert_eq!(selected(&list), Some(2));
+        assert!(list.jump_to('b'));
+        assert_eq!(selected(&list), Some(1));
+        assert!(!list.jump_to('z'));
+        assert_eq!(selected(&list), Some(1));
+
+        // Matches are cycled through without filtering anything.
+        assert!(list.find("ta", true, false));
+        assert_eq!(selected(&list), Some(4));
+        assert!(list.find("ta", true, false));
+        assert_eq!(selected(&list), Some(1));
+        assert!(list.find("ta", false, false));
+        assert_eq!(selected(&list), Some(4));
+        assert!(list.find("ta", true, true));
+        assert_eq!(selected(&list), Some(4));
+        assert_eq!(list.activated().len(), names.len());
+    }
+}
@@ -0,0 +1,25 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+/// Local HTTP server answering each connection with the next canned status and body, returns
+/// the URL of `path` on it.
+pub fn serve(path: &str, responses: Vec<(&'static str, &'static str)>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Local port");
+    let url = format!("http://{}/{}", listener.local_addr().unwrap(), path);
+    thread::spawn(move || {
+        for (status, body) in responses {
+            let (mut stream, _) = listener.accept().expect("Connection");
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+        }
+    });
+    url
+}
@@ -0,0 +1,211 @@
+use std::fs;
+
+use crate::config::Config;
+use crate::interface::game::Game;
+use crate::util::log::log;
+use crate::util::paths::theme_directory;
+
+use serde::{Deserialize, Serialize};
+use tui::style::{Color, Modifier, Style};
+
+/// Colors for every part of the interface. User themes are JSON files in the theme directory,
+/// any field left out falls back to the dark theme.
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Theme {
+    pub text: Color,
+    pub installed: Color,
+    pub uninstalled: Color,
+    pub failed: Color,
+    pub running: Color,
+    pub border: Color,
+    pub title: Color,
+    pub help: Color,
+    pub heading: Color,
+    pub highlight: Color,
+    // Proton tiers, ProtonDB's own colors by default.
+    pub platinum: Color,
+    pub gold: Color,
+    pub silver: Color,
+    pub bronze: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Theme {
+        Theme {
+            text: Color::Reset,
+            installed: Color::Reset,
+            uninstalled: Color::DarkGray,
+            failed: Color::LightRed,
+            running: Color::LightCyan,
+            border: Color::Gray,
+            title: Color::Reset,
+            help: Color::Gray,
+            heading: Color::LightYellow,
+            highlight: Color::Green,
+            platinum: Color::Rgb(180, 199, 220),
+            gold: Color::Rgb(207, 181, 59),
+            silver: Color::Rgb(166, 166, 166),
+            bronze: Color::Rgb(205, 127, 50),
+        }
+    }
+
+    pub fn light() -> Theme {
+        Theme {
+            text: Color::Reset,
+            installed: Color::Black,
+            uninstalled: Color::Gray,
+            failed: Color::Red,
+            running: Color::Blue,
+            border: Color::DarkGray,
+            title: Color::Black,
+            help: Color::DarkGray,
+            heading: Color::Blue,
+            highlight: Color::LightGreen,
+            // Darker shades of the same, the originals wash out on a light background.
+            platinum: Color::Rgb(90, 110, 140),
+            gold: Color::Rgb(160, 130, 0),
+            silver: Color::Rgb(110, 110, 110),
+            bronze: Color::Rgb(160, 90, 30),
+        }
+    }
+
+    /// Built-in themes follow the terminal's background, which is light when its luma is high.
+    pub fn auto(background: Option<(u8, u8, u8)>) -> Theme {
+        match background {
+            Some((r, g, b)) if 0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32 > 150. => {
+                Theme::light()
+            }
+            _ => Theme::dark(),
+        }
+    }
+
+    /// Resolves the `theme` field of the config: `auto`, `dark`, `light`, or the name of a file
+    /// in the theme directory (without `.json`). Built-in themes keep the config's `highlight`.
+    pub fn from_config(config: &Config, background: Option<(u8, u8, u8)>) -> Theme {
+        let builtin = |theme: Theme| Theme {
+            highlight: config.highlight,
+            ..theme
+        };
+        match config.theme.as_str() {
+            "dark" => builtin(Theme::dark()),
+            "light" => builtin(Theme::light()),
+            "auto" | "" => builtin(Theme::auto(background)),
+            name => match Theme::load(name) {
+                Some(theme) => theme,
+                None => builtin(Theme::auto(background)),
+            },
+        }
+    }
+
+    fn load(name: &str) -> Option<Theme> {
+        let path = theme_directory().ok()?.join(format!("{}.json", name));
+        match fs::read_to_string(&path).map(|data| serde_json::from_str(&data)) {
+            Ok(Ok(theme)) => Some(theme),
+            Ok(Err(err)) => {
+                log!("Invalid theme", path, err);
+                None
+            }
+            Err(err) => {
+                log!("Missing theme", path, err);
+                None
+            }
+        }
+    }
+
+    pub fn text(&self) -> Style {
+        Style::default().fg(self.text)
+    }
+
+    pub fn border(&self) -> Style {
+        Style::default().fg(self.border)
+    }
+
+    pub fn title(&self) -> Style {
+        Style::default().fg(self.title).add_modifier(Modifier::BOLD)
+    }
+
+    pub fn help(&self) -> Style {
+        Style::default().fg(self.help)
+    }
+
+    pub fn heading(&self) -> Style {
+        Style::default()
+            .fg(self.heading)
+            .add_modifier(Modifier::BOLD)
+    }
+
+    pub fn highlight(&self) -> Style {
+        Style::default()
+            .bg(self.highlight)
+            .add_modifier(Modifier::BOLD)
+    }
+
+    /// Each tier's color, native games get the running color.
+    pub fn tier(&self, tier: &str) -> Style {
+        let color = match tier.split_whitespace().next().unwrap_or("") {
+            "native" => self.running,
+            "platinum" => self.platinum,
+            "gold" => self.gold,
+            "silver" => self.silver,
+            "bronze" => self.bronze,
+            "borked" => self.failed,
+            _ => self.text,
+        };
+        Style::default().fg(color)
+    }
+
+    /// Style of a game in the list, by what it's up to.
+    pub fn game(&self, game: &Game) -> Style {
+        if game.is_running() {
+            Style::default()
+                .fg(self.running)
+                .add_modifier(Modifier::BOLD)
+        } else if game.is_failed() {
+            Style::default().fg(self.failed)
+        } else if game.is_installed() {
+            Style::default()
+                .fg(self.installed)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+                .fg(self.uninstalled)
+                .add_modifier(Modifier::DIM)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::theme::Theme;
+    use tui::style::Color;
+
+    #[test]
+    fn test_theme_selection() {
+        assert_eq!(Theme::auto(Some((250, 250, 245))), Theme::light());
+        assert_eq!(Theme::auto(Some((30, 30, 40))), Theme::dark());
+        assert_eq!(Theme::auto(None), Theme::dark());
+
+        // Partial themes fill in the rest from the dark theme.
+        let theme: Theme =
+            serde_json::from_str(r#"{"highlight": "Magenta", "failed": {"Rgb": [255, 0, 0]}}"#)
+                .expect("Valid theme");
+        assert_eq!(theme.highlight, Color::Magenta);
+        assert_eq!(theme.failed, Color::Rgb(255, 0, 0));
+        assert_eq!(theme.border, Theme::dark().border);
+
+        let theme: Theme = serde_json::from_str(r#"{"gold": "Yellow"}"#).expect("Valid theme");
+        assert_eq!(
+            theme.tier("gold (trending platinum)").fg,
+            Some(Color::Yellow)
+        );
+        assert_eq!(theme.tier("bronze").fg, Some(Theme::dark().bronze));
+        assert_eq!(theme.tier("native").fg, Some(theme.running));
+    }
+}